@@ -1,101 +1,478 @@
 //FIX remove all pubs from struct definition.
-use crate::player::Player;
+use crate::history::History;
+use crate::keymap::{Action, KeyContext, Keymap};
+use crate::notifications::Notifications;
+use crate::player::{MpvEvent, Player, SeekTarget};
+use crate::playlist_files;
+use crate::playlists::Playlists;
 use crate::queue::Queue;
 use crate::search::Search;
 use crate::settings::Settings;
-use crate::types::{Mode, Screen, Video};
+use crate::theme::{self, Theme};
+use crate::types::{self, Mode, ResultKind, Screen, Video};
 
 use crossterm::event::{self, Event, KeyEventKind};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{DefaultTerminal, widgets::ListState};
-use std::{env, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::signal::unix::{SignalKind, signal};
 
 /// The main application which holds the state and logic of the application.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct App {
     /// Is the application running?
     running: bool,
     pub player: Player,
     pub queue: Queue,
-    search: Search,
-    settings: Settings,
+    pub playlists: Playlists,
+    pub history: History,
+    pub search: Search,
+    pub settings: Settings,
+    keymap: Keymap,
+    pub theme: Theme,
     //menulist_state: ListState,
     pub resultlist: Vec<Video>,
     pub resultlist_state: ListState,
+    /// Videos queued so far by [`Action::EnqueueAll`] while it waits for the rest of the results.
+    enqueue_all: Option<usize>,
+    /// Plays the first video [`Action::EnqueueAll`] queues, for the URL ymp was started with.
+    play_enqueued: bool,
 
     pub mode: Mode,
     pub screen: Screen,
     pub search_query: String,
+    pub seek_query: String,
+    pub playlist_query: String,
+    pub path_query: String,
+    pub history_query: String,
+    /// Video waiting for a playlist to be picked in [`Mode::AddToPlaylist`].
+    pub playlist_candidate: Option<Video>,
+    pub playlist_picker_state: ListState,
+    /// Feedback like "Seek +5s" and everything that went wrong, shown as toasts and in the Log tab.
+    pub notifications: Notifications,
     pub tabs_titles: Vec<String>,
 }
 
 impl App {
-    /// Construct a new instance of [`App`].
-    fn default() -> Self {
+    /// Construct a new instance of [`App`] that runs with `settings`, `keymap` and `theme`.
+    pub fn new(settings: Settings, keymap: Keymap, theme: Theme) -> Self {
         let running = true;
-        let search = Search::new();
-        let settings = Settings::new();
-        let player = Player::new();
+        let search = Search::new(&settings);
+        let player = Player::new(&settings);
         let queue = Queue::new();
+        let playlists = Playlists::new();
+        let history = History::new(
+            settings.history_max_entries(),
+            settings.history_max_age_days(),
+        );
         let tabs_titles: Vec<String> = vec![
-            String::from("     Queue     "),
-            String::from("     Results     "),
+            String::from("  Queue  "),
+            String::from("  Results  "),
+            String::from("  Playlists  "),
+            String::from("  History  "),
+            String::from("  Log  "),
         ];
         let resultlist = Vec::new();
         let resultlist_state = ListState::default().with_selected(Some(0));
+        let enqueue_all = None;
+        let play_enqueued = false;
         let mode = Mode::default();
         let search_query = String::default();
+        let seek_query = String::default();
+        let playlist_query = String::default();
+        let path_query = String::default();
+        let history_query = String::default();
+        let playlist_candidate = None;
+        let playlist_picker_state = ListState::default().with_selected(Some(0));
+        let notifications = Notifications::new(&settings);
         let screen = Screen::Queue;
 
         Self {
             running,
             search,
             settings,
+            keymap,
+            theme,
             player,
             queue,
+            playlists,
+            history,
             tabs_titles,
             //menulist_state,
             resultlist,
             resultlist_state,
+            enqueue_all,
+            play_enqueued,
             mode,
             search_query,
+            seek_query,
+            playlist_query,
+            path_query,
+            history_query,
+            playlist_candidate,
+            playlist_picker_state,
+            notifications,
             screen,
         }
     }
-    pub fn new() -> Self {
-        Self::default()
-    }
 
     /// Run the application's main loop.
-    pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
+    pub async fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        url: Option<String>,
+    ) -> color_eyre::Result<()> {
         //self.check_dependency("yt-dlp");
         self.running = true;
-        if let Some(url) = env::args().nth(1) {
-            self.player.play_video_url(url)?;
+        if let Err(e) = self.queue.retrieve_queue() {
+            self.notifications
+                .error(format!("Could not load the queue: {e:#}"));
         }
-        self.queue.retrieve_queue()?;
-        self.settings.save()?;
+        if let Err(e) = self.playlists.retrieve_playlists() {
+            self.notifications
+                .error(format!("Could not load the playlists: {e:#}"));
+        }
+        if let Err(e) = self.history.retrieve_history() {
+            self.notifications
+                .error(format!("Could not load the history: {e:#}"));
+        }
+        if let Some(url) = url {
+            match types::result_from_url(&url) {
+                // YouTube links are looked up first, so everything goes into the queue with a title
+                Some(result) => {
+                    self.search.open(&mut self.resultlist, &result);
+                    self.enqueue_all = Some(0);
+                    self.play_enqueued = true;
+                }
+                None => {
+                    self.history.untrack();
+                    let result = self.player.play_video_url(url);
+                    self.report(result);
+                }
+            }
+        }
+        let terminated = Self::listen_for_termination()?;
 
         while self.running {
+            if terminated.load(Ordering::Relaxed) {
+                self.quit();
+                break;
+            }
             terminal.draw(|frame| self.render(frame))?;
             match self.search.check_search_results() {
-                Ok(videos) => {
+                Some(Ok(page)) if page.is_first => {
                     self.screen.select(1);
-                    self.resultlist = videos;
-                    if !self.resultlist.is_empty() {
+                    self.resultlist = page.videos;
+                    if self.resultlist.is_empty() {
+                        self.notifications.warn("The search found nothing");
+                    } else {
                         self.resultlist_state.select(Some(0));
                     }
+                    if self.enqueue_all.is_some() {
+                        let result = self.enqueue_all_results(self.resultlist.clone());
+                        self.report(result);
+                    }
                 }
-                Err(_) => {
-                    // FIX add error handling
+                Some(Ok(page)) => {
+                    // results can shift between pages while YouTube reorders them
+                    let mut added = Vec::new();
+                    for video in page.videos {
+                        if !self.resultlist.iter().any(|result| result.id == video.id) {
+                            added.push(video.clone());
+                            self.resultlist.push(video);
+                        }
+                    }
+                    let result = self.enqueue_all_results(added);
+                    self.report(result);
                 }
+                Some(Err(e)) => self.notifications.error(format!("Search failed: {e:#}")),
+                None => {}
             };
+            self.load_more_results();
+            if let Some(count) = self.enqueue_all
+                && !self.search.is_loading()
+                && self.search.is_exhausted()
+            {
+                self.enqueue_all = None;
+                self.play_enqueued = false;
+                self.notifications
+                    .info(format!("Added {count} to the queue"));
+            }
 
-            self.player.try_connect_mpv();
+            let result = self.player.try_connect_mpv();
+            self.report(result);
+            for event in self.player.poll_mpv() {
+                let result = self.handle_mpv_event(event);
+                self.report(result);
+            }
+            let result = self.record_history();
+            self.report(result);
             if event::poll(Duration::from_millis(50))? {
                 self.handle_crossterm_events()?;
             }
         }
+        // keep how far the last track got
+        self.history.save_history()?;
+        Ok(())
+    }
+
+    /// Fetches the next page of results once the cursor is on the last one, or right away while
+    /// a channel or playlist is being queued.
+    fn load_more_results(&mut self) {
+        let at_bottom = self
+            .resultlist_state
+            .selected()
+            .is_some_and(|selected| selected + 1 >= self.resultlist.len());
+        if self.enqueue_all.is_some()
+            || (self.screen == Screen::Results && !self.resultlist.is_empty() && at_bottom)
+        {
+            self.search.load_more();
+        }
+    }
+
+    /// Queues the videos of a page that came in after [`Action::EnqueueAll`], up to the
+    /// `enqueue_all_max` setting.
+    fn enqueue_all_results(&mut self, videos: Vec<Video>) -> color_eyre::Result<()> {
+        let Some(count) = self.enqueue_all else {
+            return Ok(());
+        };
+        let max = self.settings.enqueue_all_max();
+        let videos: Vec<Video> = videos
+            .into_iter()
+            .filter(|video| video.kind == ResultKind::Video)
+            .take(max.saturating_sub(count))
+            .collect();
+        let count = count + videos.len();
+        self.enqueue_all = Some(count);
+        let first = self.queue.queuelist().len();
+        let play = self.play_enqueued && !videos.is_empty();
+        for video in videos {
+            self.enqueue(video)?;
+        }
+        if play {
+            self.play_enqueued = false;
+            self.play_queue_index(first)?;
+        }
+        if count >= max {
+            self.enqueue_all = None;
+            self.play_enqueued = false;
+            self.notifications.info(format!(
+                "Added {count} to the queue, the most enqueue_all_max allows"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The result under the cursor, unless it's a channel or playlist, which only have something to
+    /// queue once they're opened.
+    fn selected_video_result(&mut self) -> Option<Video> {
+        let result = self
+            .resultlist_state
+            .selected()
+            .and_then(|index| self.resultlist.get(index))?;
+        if result.kind != ResultKind::Video {
+            self.notifications.warn(format!(
+                "{} is a {}, open it with Enter first",
+                result.title,
+                result.kind.label()
+            ));
+            return None;
+        }
+        Some(result.clone())
+    }
+
+    /// Reacts to replies and events coming back from mpv.
+    ///
+    /// Property values are already cached on [`Player`]; this is for anything [`App`] itself cares about.
+    fn handle_mpv_event(&mut self, event: MpvEvent) -> color_eyre::Result<()> {
+        match event {
+            MpvEvent::TimePos(Some(position)) => self.history.update_played_for(position),
+            MpvEvent::Volume(_)
+            | MpvEvent::TimePos(_)
+            | MpvEvent::Duration(_)
+            | MpvEvent::Pause(_) => {}
+            MpvEvent::EndFile(_) => {}
+            MpvEvent::PlaylistPos(pos) if self.player.is_playlist() => {
+                // queue edits renumber mpv's entries too; the current one having a new index is fine
+                let renumbered = pos.is_some() && pos == self.queue.current_index();
+                if !self.player.playlist_pos_is_expected(pos) && !renumbered {
+                    self.follow_playlist(pos)?;
+                }
+            }
+            MpvEvent::PlaylistPos(_) => {}
+            // Advance when the track ran out (or failed to play) but not when the user quit mpv.
+            // No reason at all means mpv had no IPC connection, so assume it just finished.
+            MpvEvent::Exited(reason) => match reason.as_deref() {
+                Some("eof") | None => self.play_next()?,
+                Some("error") => {
+                    let title = self.player.now_playing().title.clone();
+                    self.notifications
+                        .warn(format!("mpv could not play {title}"));
+                    self.play_next()?;
+                }
+                Some(_) => {}
+            },
+            MpvEvent::Error(message) => self.notifications.error(message),
+        }
+        Ok(())
+    }
+
+    /// Adds whatever the player started since the last call to the play history.
+    fn record_history(&mut self) -> color_eyre::Result<()> {
+        let started = self.player.take_started();
+        if started.is_empty() {
+            return Ok(());
+        }
+        for video in started {
+            self.history.record(video);
+        }
+        self.history.save_history()
+    }
+
+    /// Plays the queue entry at `index`, through mpv's own playlist when gapless playback is on.
+    fn play_queue_index(&mut self, index: usize) -> color_eyre::Result<()> {
+        let video = self.queue.play_index(index)?;
+        self.play_current(index, video)
+    }
+
+    /// Starts `video`, the queue's current entry at `index`, through mpv's own playlist when
+    /// gapless playback is on.
+    fn play_current(&mut self, index: usize, video: Video) -> color_eyre::Result<()> {
+        if !self.settings.gapless() {
+            return self.player.play_video(&video);
+        }
+        if self.player.is_playlist() {
+            self.player.set_now_playing(video);
+            self.player.playlist_jump(index)
+        } else {
+            let repeat_mode = self.queue.repeat_mode();
+            self.player
+                .play_playlist(self.queue.queuelist(), index, repeat_mode)
+        }
+    }
+
+    /// mpv moved through its playlist by itself. Its playlist mirrors the displayed queue, so check
+    /// the move against the queue's own repeat/shuffle order and correct mpv when they disagree.
+    fn follow_playlist(&mut self, pos: Option<usize>) -> color_eyre::Result<()> {
+        match self.queue.play_next() {
+            Some(video) => {
+                let next = self.queue.current_index();
+                if next != pos
+                    && let Some(next) = next
+                {
+                    self.player.playlist_jump(next)?;
+                }
+                self.player.set_now_playing(video);
+            }
+            // the queue's order is finished, even if mpv's playlist isn't
+            None => self.player.stop()?,
+        }
+        Ok(())
+    }
+
+    /// Removes the entry under the cursor. If it was playing, playback moves on to the entry that
+    /// took its place.
+    fn remove_selected(&mut self) -> color_eyre::Result<()> {
+        let Some(index) = self.queue.queuelist_state().selected() else {
+            return Ok(());
+        };
+        if index >= self.queue.queuelist().len() {
+            return Ok(());
+        }
+        let was_current = self.queue.current_index() == Some(index);
+        self.queue.remove(index)?;
+        self.queue.clamp_selection();
+        self.queue.save_queue()?;
+        if self.player.is_playlist() {
+            // mpv skips to the next entry by itself; just keep the queue in step with it
+            self.player.playlist_remove(index)?;
+            if was_current && index < self.queue.queuelist().len() {
+                let video = self.queue.play_index(index)?;
+                self.player.set_now_playing(video);
+            }
+        } else if was_current && *self.player.is_nowplaying() {
+            if index < self.queue.queuelist().len() {
+                self.play_queue_index(index)?;
+            } else {
+                self.player.stop()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the entry under the cursor `offset` places up (negative) or down, taking the cursor along.
+    fn move_selected(&mut self, offset: isize) -> color_eyre::Result<()> {
+        let Some(from) = self.queue.queuelist_state().selected() else {
+            return Ok(());
+        };
+        let Some(to) = from.checked_add_signed(offset) else {
+            return Ok(());
+        };
+        if from >= self.queue.queuelist().len() || to >= self.queue.queuelist().len() {
+            return Ok(());
+        }
+        self.queue.move_entry(from, to)?;
+        self.queue.queuelist_state().select(Some(to));
+        self.queue.save_queue()?;
+        if self.player.is_playlist() {
+            self.player.playlist_move(from, to)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds mpv's playlist from the queue after edits too big to mirror one by one.
+    fn sync_playlist(&mut self) -> color_eyre::Result<()> {
+        if self.player.is_playlist() {
+            let current_index = self.queue.current_index();
+            self.player
+                .playlist_sync(self.queue.queuelist(), current_index)?;
+        }
+        Ok(())
+    }
+
+    fn toggle_gapless(&mut self) -> color_eyre::Result<()> {
+        self.settings.toggle_gapless();
+        self.settings.save()?;
+        let state = if self.settings.gapless() { "on" } else { "off" };
+        self.notifications
+            .info(format!("Gapless playback {state} (from next play)"));
+        Ok(())
+    }
+
+    /// Switches to the next built-in theme and keeps it for next time.
+    fn cycle_theme(&mut self) -> color_eyre::Result<()> {
+        let current = theme::PRESETS
+            .iter()
+            .position(|name| *name == self.settings.theme())
+            .unwrap_or(0);
+        let name = theme::PRESETS[(current + 1) % theme::PRESETS.len()];
+        let theme = Theme::new(name, self.settings.colors())?;
+        if theme.name() != name {
+            // NO_COLOR wins over any theme
+            self.notifications.warn("NO_COLOR is set, colors are off");
+            return Ok(());
+        }
+        self.theme = theme;
+        self.settings.set_theme(name);
+        self.settings.save()?;
+        self.notifications.info(format!("Theme: {name}"));
+        Ok(())
+    }
+
+    /// Starts the queue entry after the one that just finished, if there is one.
+    fn play_next(&mut self) -> color_eyre::Result<()> {
+        if let Some(video) = self.queue.play_next()
+            && let Some(index) = self.queue.current_index()
+        {
+            self.play_current(index, video)?;
+        }
         Ok(())
     }
 
@@ -106,15 +483,22 @@ impl App {
     fn handle_crossterm_events(&mut self) -> color_eyre::Result<()> {
         match event::read()? {
             // it's important to check KeyEventKind::Press to avoid handling key release events
-            Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key),
-            Event::Mouse(_) => Ok(()),
-            Event::Resize(_, _) => Ok(()),
-            _ => Ok(()),
-        }?;
+            // a key that fails shows up as a notification, only a broken terminal ends the app
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                let result = self.on_key_event(key);
+                self.report(result);
+            }
+            Event::Mouse(_) => {}
+            Event::Resize(_, _) => {}
+            _ => {}
+        }
         Ok(())
     }
 
     fn on_key_event(&mut self, key: KeyEvent) -> color_eyre::Result<()> {
+        if !matches!(self.mode, Mode::AddToPlaylist | Mode::Default) {
+            self.keymap.clear_pending();
+        }
         match self.mode {
             Mode::Search => match key.code {
                 KeyCode::Char('c' | 'C') => {
@@ -129,8 +513,19 @@ impl App {
                     self.search_query.pop();
                 }
                 KeyCode::Enter => {
-                    self.search
-                        .search(&mut self.resultlist, self.search_query.to_owned());
+                    self.enqueue_all = None;
+                    self.play_enqueued = false;
+                    // links are opened rather than searched for
+                    let result = match types::result_from_url(&self.search_query) {
+                        Some(result) => {
+                            self.search.open(&mut self.resultlist, &result);
+                            Ok(())
+                        }
+                        None => self
+                            .search
+                            .search(&mut self.resultlist, self.search_query.to_owned()),
+                    };
+                    self.report(result);
                     self.search_query = String::new();
                     self.mode = Mode::Default;
                     self.screen = Screen::Results;
@@ -141,89 +536,574 @@ impl App {
                 }
                 _ => {}
             },
-            Mode::Default => {
-                if self.screen == Screen::Results {
-                    match key.code {
-                        KeyCode::Char('q' | 'Q') => self.quit(),
-                        KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => {
-                            self.quit()
-                        }
-                        KeyCode::Char('H') => {
-                            self.screen.next();
-                        }
-                        KeyCode::Char('L') => {
-                            self.screen.previous();
-                        }
-                        KeyCode::Char('j') => self.resultlist_state.select_next(),
-                        KeyCode::Char('k') => self.resultlist_state.select_previous(),
-                        KeyCode::Enter => {
-                            if self.queue.queuelist().is_empty() {
-                                self.queue
-                                    .add_to_queue(&self.resultlist, &self.resultlist_state)?;
-                                self.player.play_video(&mut self.queue)?;
-                            } else {
-                                self.queue
-                                    .add_to_queue(&self.resultlist, &self.resultlist_state)?;
-                            }
-                            self.queue.save_queue()?;
-                            self.screen.select(0);
-                        }
-                        KeyCode::Char('/') => self.mode = Mode::Search,
-                        KeyCode::Char('m') => self.player.playback_mode_switch(),
-                        _ => {}
-                    }
-                } else if self.screen == Screen::Queue {
-                    match key.code {
-                        KeyCode::Char('q' | 'Q') => self.quit(),
-                        KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => {
-                            self.quit()
-                        }
-                        KeyCode::Char('C') => {
-                            self.queue.queuelist().clear();
-                            self.queue.save_queue()?;
-                        }
-                        KeyCode::Char('H') => {
-                            self.screen.next();
-                        }
-                        KeyCode::Char('L') => {
-                            self.screen.previous();
-                        }
-                        KeyCode::Char('j') => self.queue.queuelist_state().select_next(),
-                        KeyCode::Char('k') => self.queue.queuelist_state().select_previous(),
-                        KeyCode::Enter => self.player.play_video(&mut self.queue)?,
-                        KeyCode::Char('/') => self.mode = Mode::Search,
-                        KeyCode::Char('m') => self.player.playback_mode_switch(),
-                        KeyCode::Esc | KeyCode::Char('s') => {
-                            self.player.stop()?;
-                            self.mode = Mode::Default;
+            Mode::Seek => match key.code {
+                KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => self.quit(),
+                KeyCode::Char(ch) => self.seek_query.push(ch),
+                KeyCode::Backspace => {
+                    self.seek_query.pop();
+                }
+                KeyCode::Enter => {
+                    match SeekTarget::parse(&self.seek_query) {
+                        Some(target) => self.seek(target)?,
+                        None => self
+                            .notifications
+                            .warn(format!("Invalid timestamp: {}", self.seek_query)),
+                    }
+                    self.seek_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                KeyCode::Esc => {
+                    self.seek_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                _ => {}
+            },
+            Mode::NewPlaylist | Mode::RenamePlaylist => match key.code {
+                KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => self.quit(),
+                KeyCode::Char(ch) => self.playlist_query.push(ch),
+                KeyCode::Backspace => {
+                    self.playlist_query.pop();
+                }
+                KeyCode::Enter => {
+                    let result = if self.mode == Mode::NewPlaylist {
+                        self.playlists.create(&self.playlist_query)
+                    } else {
+                        match self.playlists.playlists_state().selected() {
+                            Some(index) => self.playlists.rename(index, &self.playlist_query),
+                            None => Ok(()),
                         }
-                        KeyCode::Char('9') => {
-                            if *self.player.is_nowplaying() {
-                                self.player.decrease_volume()?;
-                                self.player.get_current_volume()?;
-                            }
+                    };
+                    match result {
+                        Ok(()) => self.playlists.save_playlists()?,
+                        Err(e) => self.notifications.error(e.to_string()),
+                    }
+                    self.playlist_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                KeyCode::Esc => {
+                    self.playlist_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                _ => {}
+            },
+            Mode::Import | Mode::Export => match key.code {
+                KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => self.quit(),
+                KeyCode::Char(ch) => self.path_query.push(ch),
+                KeyCode::Backspace => {
+                    self.path_query.pop();
+                }
+                KeyCode::Enter => {
+                    let path = expand_home(self.path_query.trim());
+                    let result = if self.mode == Mode::Import {
+                        self.import_from(&path)
+                    } else {
+                        self.export_to(&path)
+                    };
+                    if let Err(e) = result {
+                        self.notifications.error(e.to_string());
+                    }
+                    self.path_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                KeyCode::Esc => {
+                    self.path_query = String::new();
+                    self.mode = Mode::Default;
+                }
+                _ => {}
+            },
+            Mode::FilterHistory => match key.code {
+                KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => self.quit(),
+                KeyCode::Char(ch) => {
+                    self.history_query.push(ch);
+                    self.history.set_filter(self.history_query.clone());
+                }
+                KeyCode::Backspace => {
+                    self.history_query.pop();
+                    self.history.set_filter(self.history_query.clone());
+                }
+                KeyCode::Enter => self.mode = Mode::Default,
+                KeyCode::Esc => {
+                    self.history_query = String::new();
+                    self.history.set_filter(String::new());
+                    self.mode = Mode::Default;
+                }
+                _ => {}
+            },
+            Mode::AddToPlaylist | Mode::Default => {
+                let context = match (&self.mode, &self.screen) {
+                    (Mode::AddToPlaylist, _) => KeyContext::Picker,
+                    (_, Screen::Queue) => KeyContext::Queue,
+                    (_, Screen::Results) => KeyContext::Results,
+                    (_, Screen::Playlists) => KeyContext::Playlists,
+                    (_, Screen::History) => KeyContext::History,
+                    (_, Screen::Log) => KeyContext::Log,
+                };
+                if let Some(action) = self.keymap.handle(context, key) {
+                    if self.mode == Mode::AddToPlaylist {
+                        self.perform_picker(action)?;
+                    } else {
+                        self.perform(action)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Carries out a bound [`Action`] on the current screen. Actions that mean nothing there are ignored.
+    fn perform(&mut self, action: Action) -> color_eyre::Result<()> {
+        match action {
+            Action::Quit => self.quit(),
+            Action::NextTab => self.screen.next(),
+            Action::PreviousTab => self.screen.previous(),
+            Action::SelectNext => self.list_state().select_next(),
+            Action::SelectPrevious => self.list_state().select_previous(),
+            Action::SelectFirst => self.list_state().select_first(),
+            Action::SelectLast => self.list_state().select_last(),
+            Action::Search => self.mode = Mode::Search,
+            Action::SwitchPlaybackMode => {
+                let index = self.queue.current_index();
+                self.player
+                    .playback_mode_switch(self.queue.queuelist(), index)?;
+            }
+            Action::CycleRepeat => {
+                self.queue.cycle_repeat_mode();
+                self.queue.save_queue()?;
+                if self.player.is_playlist() {
+                    self.player.playlist_set_repeat(self.queue.repeat_mode())?;
+                }
+            }
+            Action::ToggleShuffle => {
+                self.queue.toggle_shuffle();
+                self.queue.save_queue()?;
+            }
+            Action::ToggleGapless => self.toggle_gapless()?,
+            Action::CycleTheme => self.cycle_theme()?,
+            Action::PlayPause if *self.player.is_nowplaying() => self.player.play_pause()?,
+            Action::Stop => {
+                self.player.stop()?;
+                self.mode = Mode::Default;
+            }
+            Action::VolumeUp if *self.player.is_nowplaying() => {
+                self.player.increase_volume()?;
+                self.player.get_current_volume()?;
+            }
+            Action::VolumeDown if *self.player.is_nowplaying() => {
+                self.player.decrease_volume()?;
+                self.player.get_current_volume()?;
+            }
+            Action::SeekForward => self.seek(SeekTarget::Relative(self.settings.seek_step()))?,
+            Action::SeekBackward => self.seek(SeekTarget::Relative(-self.settings.seek_step()))?,
+            Action::SeekStart => self.seek(SeekTarget::Absolute(0.0))?,
+            Action::SeekPercent(percent) => self.seek(SeekTarget::Percent(f64::from(percent)))?,
+            Action::SeekPrompt if *self.player.is_nowplaying() => self.mode = Mode::Seek,
+            Action::Confirm => self.confirm()?,
+            Action::AddToPlaylist => {
+                let video = match self.screen {
+                    Screen::Queue => self
+                        .queue
+                        .queuelist_state()
+                        .selected()
+                        .and_then(|index| self.queue.queuelist().get(index))
+                        .cloned(),
+                    Screen::Results => self.selected_video_result(),
+                    Screen::History => self.history.selected().map(|entry| entry.video.clone()),
+                    Screen::Playlists | Screen::Log => None,
+                };
+                self.pick_playlist_for(video);
+            }
+            Action::AppendToQueue => match self.screen {
+                Screen::Playlists => {
+                    if let Some(playlist) = self.playlists.selected() {
+                        let videos = playlist.videos.clone();
+                        let count = videos.len();
+                        for video in videos {
+                            self.enqueue(video)?;
                         }
-                        KeyCode::Char('0') => {
-                            if *self.player.is_nowplaying() {
-                                self.player.increase_volume()?;
-                            }
+                        self.notifications
+                            .info(format!("Added {count} to the queue"));
+                    }
+                }
+                Screen::History => {
+                    if let Some(entry) = self.history.selected() {
+                        let video = entry.video.clone();
+                        self.enqueue(video)?;
+                        self.notifications.info(String::from("Added to the queue"));
+                    }
+                }
+                Screen::Results => {
+                    if let Some(video) = self.selected_video_result() {
+                        self.enqueue(video)?;
+                        self.notifications.info(String::from("Added to the queue"));
+                    }
+                }
+                Screen::Queue | Screen::Log => {}
+            },
+            Action::PlayNext => match self.screen {
+                Screen::Queue => {
+                    if let Some(index) = self.queue.queuelist_state().selected()
+                        && index < self.queue.queuelist().len()
+                    {
+                        let target = self.queue.play_next_from(index)?;
+                        self.queue.queuelist_state().select(Some(target));
+                        self.queue.save_queue()?;
+                        if self.player.is_playlist() {
+                            self.player.playlist_move(index, target)?;
                         }
-                        KeyCode::Char(' ') => {
-                            if *self.player.is_nowplaying() {
-                                self.player.play_pause()?;
-                            }
+                    }
+                }
+                Screen::Results => {
+                    if let Some(video) = self.selected_video_result() {
+                        self.enqueue(video)?;
+                        let index = self.queue.queuelist().len() - 1;
+                        let target = self.queue.play_next_from(index)?;
+                        if self.player.is_playlist() {
+                            self.player.playlist_move(index, target)?;
                         }
-                        _ => {}
+                        self.queue.save_queue()?;
+                        self.notifications.info(String::from("Playing next"));
+                    }
+                }
+                Screen::Playlists | Screen::History | Screen::Log => {}
+            },
+            Action::Remove => match self.screen {
+                Screen::Queue => self.remove_selected()?,
+                Screen::Playlists => {
+                    if let Some(index) = self.playlists.playlists_state().selected()
+                        && index < self.playlists.playlists().len()
+                    {
+                        let playlist = self.playlists.delete(index)?;
+                        self.playlists.save_playlists()?;
+                        self.notifications
+                            .info(format!("Deleted playlist {}", playlist.name));
+                    }
+                }
+                Screen::History => {
+                    if let Some(index) = self.history.history_state().selected()
+                        && self.history.selected().is_some()
+                    {
+                        self.history.remove(index)?;
+                        self.history.save_history()?;
+                    }
+                }
+                Screen::Results | Screen::Log => {}
+            },
+            Action::Clear => match self.screen {
+                Screen::Queue => {
+                    self.queue.clear();
+                    self.queue.save_queue()?;
+                    // the playing track carries on to its end, gapless or not
+                    if self.player.is_playlist() {
+                        self.player.playlist_release()?;
+                    }
+                }
+                Screen::History => {
+                    self.history.clear();
+                    self.history.save_history()?;
+                    self.notifications.info(String::from("History cleared"));
+                }
+                Screen::Log => self.notifications.clear_log(),
+                Screen::Results | Screen::Playlists => {}
+            },
+            Action::MoveDown if self.screen == Screen::Queue => self.move_selected(1)?,
+            Action::MoveUp if self.screen == Screen::Queue => self.move_selected(-1)?,
+            Action::RemoveDuplicates if self.screen == Screen::Queue => {
+                let removed = self.queue.remove_duplicates();
+                self.queue.clamp_selection();
+                self.queue.save_queue()?;
+                self.sync_playlist()?;
+                self.notifications
+                    .info(format!("Removed {removed} duplicates"));
+            }
+            Action::Undo if self.screen == Screen::Queue => {
+                if self.queue.undo() {
+                    self.queue.save_queue()?;
+                    self.sync_playlist()?;
+                    self.notifications.info(String::from("Undone"));
+                } else {
+                    self.notifications.info(String::from("Nothing to undo"));
+                }
+            }
+            Action::Import if matches!(self.screen, Screen::Queue | Screen::Playlists) => {
+                self.mode = Mode::Import
+            }
+            Action::Export
+                if self.screen == Screen::Queue
+                    || (self.screen == Screen::Playlists
+                        && self.playlists.selected().is_some()) =>
+            {
+                self.mode = Mode::Export
+            }
+            Action::NewPlaylist if self.screen == Screen::Playlists => {
+                self.mode = Mode::NewPlaylist
+            }
+            Action::RenamePlaylist if self.screen == Screen::Playlists => {
+                if let Some(playlist) = self.playlists.selected() {
+                    self.playlist_query = playlist.name.clone();
+                    self.mode = Mode::RenamePlaylist;
+                }
+            }
+            Action::DismissNotifications => self.notifications.dismiss(),
+            // queues everything listed, then whatever else the channel or playlist still has
+            Action::EnqueueAll if self.screen == Screen::Results => {
+                self.enqueue_all = Some(0);
+                let result = self.enqueue_all_results(self.resultlist.clone());
+                self.report(result);
+                // more search results would each take another, slower yt-dlp run from the top
+                if !self.search.is_listing()
+                    && let Some(count) = self.enqueue_all.take()
+                {
+                    self.notifications
+                        .info(format!("Added {count} to the queue"));
+                }
+            }
+            Action::Cancel if self.search.is_loading() || self.enqueue_all.is_some() => {
+                self.search.cancel();
+                match self.enqueue_all.take() {
+                    Some(count) => {
+                        self.play_enqueued = false;
+                        self.notifications
+                            .info(format!("Stopped after adding {count} to the queue"));
                     }
+                    None => self.notifications.info("Search cancelled"),
+                }
+            }
+            // with nothing to cancel, Esc stops playback on the queue as it always has
+            Action::Cancel if self.screen == Screen::Queue => self.perform(Action::Stop)?,
+            Action::Filter if self.screen == Screen::History => {
+                self.history_query = self.history.filter().to_string();
+                self.mode = Mode::FilterHistory;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Enter on the current screen.
+    fn confirm(&mut self) -> color_eyre::Result<()> {
+        match self.screen {
+            Screen::Queue => {
+                if let Some(index) = self.queue.queuelist_state().selected() {
+                    self.play_queue_index(index)?;
+                }
+            }
+            // lists what's in a channel or playlist in place of the results
+            Screen::Results
+                if let Some(result) = self
+                    .resultlist_state
+                    .selected()
+                    .and_then(|index| self.resultlist.get(index))
+                    .filter(|result| result.kind != ResultKind::Video)
+                    .cloned() =>
+            {
+                self.enqueue_all = None;
+                self.play_enqueued = false;
+                self.search.open(&mut self.resultlist, &result);
+                self.resultlist_state.select(Some(0));
+            }
+            Screen::Results => {
+                if self.queue.queuelist().is_empty() {
+                    self.queue
+                        .add_to_queue(&self.resultlist, &self.resultlist_state)?;
+                    self.play_queue_index(0)?;
+                } else {
+                    self.queue
+                        .add_to_queue(&self.resultlist, &self.resultlist_state)?;
+                    if self.player.is_playlist()
+                        && let Some(video) = self.queue.queuelist().last().cloned()
+                    {
+                        self.player.playlist_append(&video)?;
+                    }
+                }
+                self.queue.save_queue()?;
+                self.screen.select(0);
+            }
+            // swaps the queue for the playlist (undo with u on the Queue tab)
+            Screen::Playlists => {
+                if let Some(playlist) = self.playlists.selected() {
+                    let videos = playlist.videos.clone();
+                    self.queue.replace(videos);
+                    self.queue.save_queue()?;
+                    self.sync_playlist()?;
+                    self.screen = Screen::Queue;
+                }
+            }
+            // plays the entry again from the end of the queue
+            Screen::History => {
+                if let Some(entry) = self.history.selected() {
+                    let video = entry.video.clone();
+                    self.enqueue(video)?;
+                    let index = self.queue.queuelist().len() - 1;
+                    self.play_queue_index(index)?;
+                }
+            }
+            Screen::Log => {}
+        }
+        Ok(())
+    }
+
+    /// Keys of the "Add to Playlist" picker.
+    fn perform_picker(&mut self, action: Action) -> color_eyre::Result<()> {
+        match action {
+            Action::Quit => self.quit(),
+            Action::SelectNext => self.playlist_picker_state.select_next(),
+            Action::SelectPrevious => self.playlist_picker_state.select_previous(),
+            Action::SelectFirst => self.playlist_picker_state.select_first(),
+            Action::SelectLast => self.playlist_picker_state.select_last(),
+            Action::Confirm => {
+                if let Some(index) = self.playlist_picker_state.selected()
+                    && let Some(video) = self.playlist_candidate.take()
+                    && index < self.playlists.playlists().len()
+                {
+                    let name = self.playlists.playlists()[index].name.clone();
+                    self.playlists.add_video(index, video)?;
+                    self.playlists.save_playlists()?;
+                    self.notifications.info(format!("Added to {name}"));
+                }
+                self.playlist_candidate = None;
+                self.mode = Mode::Default;
+            }
+            Action::Cancel => {
+                self.playlist_candidate = None;
+                self.mode = Mode::Default;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The list the cursor moves in on the current screen.
+    fn list_state(&mut self) -> &mut ListState {
+        match self.screen {
+            Screen::Queue => self.queue.queuelist_state(),
+            Screen::Results => &mut self.resultlist_state,
+            Screen::Playlists => self.playlists.playlists_state(),
+            Screen::History => self.history.history_state(),
+            Screen::Log => self.notifications.log_state(),
+        }
+    }
+
+    /// Appends `video` to the queue, and to mpv's playlist in gapless mode.
+    fn enqueue(&mut self, video: Video) -> color_eyre::Result<()> {
+        if self.player.is_playlist() {
+            self.player.playlist_append(&video)?;
+        }
+        self.queue.push(video);
+        self.queue.save_queue()
+    }
+
+    /// Imports a playlist file: into the queue from the Queue tab, or as a new playlist named after
+    /// the file from the Playlists tab.
+    fn import_from(&mut self, path: &Path) -> color_eyre::Result<()> {
+        let videos = playlist_files::import(path)?;
+        let count = videos.len();
+        if self.screen == Screen::Playlists {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let index = match self.playlists.position(&name) {
+                Some(index) => index,
+                None => {
+                    self.playlists.create(&name)?;
+                    self.playlists.playlists().len() - 1
                 }
+            };
+            for video in videos {
+                self.playlists.add_video(index, video)?;
             }
+            self.playlists.save_playlists()?;
+            self.notifications
+                .info(format!("Imported {count} into {name}"));
+        } else {
+            for video in videos {
+                if self.player.is_playlist() {
+                    self.player.playlist_append(&video)?;
+                }
+                self.queue.push(video);
+            }
+            self.queue.save_queue()?;
+            self.notifications
+                .info(format!("Imported {count} into the queue"));
+        }
+        Ok(())
+    }
+
+    /// Exports the queue, or the playlist under the cursor on the Playlists tab.
+    fn export_to(&mut self, path: &Path) -> color_eyre::Result<()> {
+        let (title, videos) = match self.playlists.selected() {
+            Some(playlist) if self.screen == Screen::Playlists => {
+                (playlist.name.clone(), playlist.videos.clone())
+            }
+            _ => (String::from("Queue"), self.queue.queuelist().clone()),
+        };
+        playlist_files::export(&videos, &title, path)?;
+        self.notifications
+            .info(format!("Exported {} to {}", videos.len(), path.display()));
+        Ok(())
+    }
+
+    /// Opens the playlist picker for `video`, if there is a video and a playlist to put it in.
+    fn pick_playlist_for(&mut self, video: Option<Video>) {
+        let Some(video) = video else {
+            return;
+        };
+        if self.playlists.playlists().is_empty() {
+            self.notifications.info(String::from(
+                "No playlists yet. Create one with N on the Playlists tab",
+            ));
+            return;
+        }
+        self.playlist_candidate = Some(video);
+        self.mode = Mode::AddToPlaylist;
+    }
+
+    fn seek(&mut self, target: SeekTarget) -> color_eyre::Result<()> {
+        if *self.player.is_nowplaying() {
+            self.player.seek(target)?;
+            self.notifications.info(format!("Seek {target}"));
         }
         Ok(())
     }
 
+    /// Shows a failed action as an error notification instead of letting it end the app.
+    fn report(&mut self, result: color_eyre::Result<()>) {
+        if let Err(e) = result {
+            self.notifications.error(format!("{e:#}"));
+        }
+    }
+
     /// Set running to false to quit the application.
     fn quit(&mut self) {
         self.player.kill_mpv();
         self.running = false;
     }
+
+    /// Flag set once ymp is asked to terminate by SIGTERM, SIGHUP or SIGINT, so it can quit like it
+    /// does on `q` instead of dying with mpv still playing and the terminal left in raw mode.
+    fn listen_for_termination() -> color_eyre::Result<Arc<AtomicBool>> {
+        let terminated = Arc::new(AtomicBool::new(false));
+        for kind in [
+            SignalKind::terminate(),
+            SignalKind::hangup(),
+            SignalKind::interrupt(),
+        ] {
+            let mut signal = signal(kind)?;
+            let terminated = Arc::clone(&terminated);
+            tokio::spawn(async move {
+                if signal.recv().await.is_some() {
+                    terminated.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        Ok(terminated)
+    }
+}
+
+/// Expands a leading `~` to the home directory, as a shell would.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
 }
@@ -0,0 +1,163 @@
+use crate::playlist_files;
+use crate::playlists::Playlists;
+use crate::queue::Queue;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: ymp [--config <FILE>] [--set <KEY=VALUE>]... [--log-level <LEVEL>] [URL]
+       ymp --import <FILE> [--playlist <NAME>]
+       ymp --export <FILE> [--playlist <NAME>]
+
+  URL                 Play a YouTube URL right away
+  --config <FILE>     Read settings from FILE instead of the default settings.json
+  --set <KEY=VALUE>   Override a setting for this run, e.g. --set seek_step=10
+  --log-level <LEVEL> How much to write to ymp.log: off, error, warn, info, debug or trace
+  --import <FILE>     Add the videos in FILE to the queue, or to playlist NAME
+  --export <FILE>     Write the queue, or playlist NAME, to FILE
+  --playlist <NAME>   Playlist to import into (created if missing) or export from
+  -h, --help          Show this help
+
+FILE formats are picked by extension: .m3u/.m3u8, .xspf, anything else is one URL per line.
+Settings can also be overridden with YMP_<KEY> environment variables, e.g. YMP_GAPLESS=true.";
+
+/// Work ymp does straight from the command line, without starting the TUI.
+#[derive(Debug, PartialEq)]
+pub enum CliCommand {
+    Import {
+        path: PathBuf,
+        playlist: Option<String>,
+    },
+    Export {
+        path: PathBuf,
+        playlist: Option<String>,
+    },
+    Help,
+}
+
+/// Parsed command line arguments.
+#[derive(Debug, Default)]
+pub struct Cli {
+    /// URL to play once the TUI is up.
+    pub url: Option<String>,
+    pub command: Option<CliCommand>,
+    /// Settings file given with `--config`.
+    pub config: Option<PathBuf>,
+    /// `key=value` settings overrides given with `--set`.
+    pub settings: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> color_eyre::Result<Self> {
+        let mut cli = Cli::default();
+        let (mut import, mut export, mut playlist) = (None, None, None);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => cli.command = Some(CliCommand::Help),
+                "--import" => import = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--export" => export = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--playlist" => playlist = Some(Self::value(&arg, args.next())?),
+                "--config" => cli.config = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--set" => cli.settings.push(Self::value(&arg, args.next())?),
+                // shorthand for --set log_level=LEVEL
+                "--log-level" => cli
+                    .settings
+                    .push(format!("log_level={}", Self::value(&arg, args.next())?)),
+                _ if arg.starts_with('-') => {
+                    return Err(color_eyre::eyre::eyre!("Unknown option {arg}\n\n{USAGE}"));
+                }
+                _ => cli.url = Some(arg),
+            }
+        }
+        if cli.command.is_some() {
+            return Ok(cli);
+        }
+        // import and export don't read the settings or start playing anything
+        if (import.is_some() || export.is_some())
+            && (cli.url.is_some() || cli.config.is_some() || !cli.settings.is_empty())
+        {
+            return Err(color_eyre::eyre::eyre!(
+                "--import and --export can't be combined with a URL, --config, --set or --log-level"
+            ));
+        }
+        cli.command = match (import, export) {
+            (Some(_), Some(_)) => {
+                return Err(color_eyre::eyre::eyre!(
+                    "--import and --export can't be used together"
+                ));
+            }
+            (Some(path), None) => Some(CliCommand::Import { path, playlist }),
+            (None, Some(path)) => Some(CliCommand::Export { path, playlist }),
+            (None, None) if playlist.is_some() => {
+                return Err(color_eyre::eyre::eyre!(
+                    "--playlist needs --import or --export"
+                ));
+            }
+            (None, None) => None,
+        };
+        Ok(cli)
+    }
+
+    fn value(option: &str, value: Option<String>) -> color_eyre::Result<String> {
+        value.ok_or_else(|| color_eyre::eyre::eyre!("{option} needs a value\n\n{USAGE}"))
+    }
+}
+
+impl CliCommand {
+    pub fn run(&self) -> color_eyre::Result<()> {
+        match self {
+            CliCommand::Help => println!("{USAGE}"),
+            CliCommand::Import { path, playlist } => {
+                let videos = playlist_files::import(path)?;
+                let count = videos.len();
+                match playlist {
+                    Some(name) => {
+                        let mut playlists = Playlists::new();
+                        playlists.retrieve_playlists()?;
+                        let index = match playlists.position(name) {
+                            Some(index) => index,
+                            None => {
+                                playlists.create(name)?;
+                                playlists.playlists().len() - 1
+                            }
+                        };
+                        for video in videos {
+                            playlists.add_video(index, video)?;
+                        }
+                        playlists.save_playlists()?;
+                        println!("Imported {count} videos into playlist {name}");
+                    }
+                    None => {
+                        let mut queue = Queue::new();
+                        queue.retrieve_queue()?;
+                        for video in videos {
+                            queue.push(video);
+                        }
+                        queue.save_queue()?;
+                        println!("Imported {count} videos into the queue");
+                    }
+                }
+            }
+            CliCommand::Export { path, playlist } => {
+                let (title, videos) = match playlist {
+                    Some(name) => {
+                        let mut playlists = Playlists::new();
+                        playlists.retrieve_playlists()?;
+                        let index = playlists
+                            .position(name)
+                            .ok_or_else(|| color_eyre::eyre::eyre!("No playlist named {name}"))?;
+                        let playlist = &playlists.playlists()[index];
+                        (playlist.name.clone(), playlist.videos.clone())
+                    }
+                    None => {
+                        let mut queue = Queue::new();
+                        queue.retrieve_queue()?;
+                        (String::from("Queue"), queue.queuelist().clone())
+                    }
+                };
+                playlist_files::export(&videos, &title, path)?;
+                println!("Exported {} videos to {}", videos.len(), path.display());
+            }
+        }
+        Ok(())
+    }
+}
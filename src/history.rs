@@ -0,0 +1,301 @@
+use crate::types::Video;
+use ratatui::widgets::ListState;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub video: Video,
+    /// Unix timestamp (seconds) of when playback started.
+    pub played_at: u64,
+    /// Seconds actually played, not counting what was skipped by seeking.
+    #[serde(default)]
+    pub played_for: f64,
+}
+
+/// Every track ymp started playing, oldest first. Kept in `history.json`.
+#[derive(Default, Debug)]
+pub struct History {
+    history_path: String,
+    entries: Vec<HistoryEntry>,
+    history_state: ListState,
+    /// Only entries whose title or uploader contain this are shown.
+    filter: String,
+    /// Most entries kept, 0 for no limit.
+    max_entries: usize,
+    /// Entries older than this many days are dropped, 0 to keep them forever.
+    max_age_days: u64,
+    /// Entry of the track playing now, `None` if that track isn't in the history.
+    current: Option<usize>,
+    /// Last `time-pos` of the current entry and when it came in.
+    last_position: Option<(f64, Instant)>,
+}
+
+impl History {
+    pub fn default() -> Self {
+        let history_path = Self::init_history_path();
+        let entries = Vec::new();
+        let history_state = ListState::default().with_selected(Some(0));
+        let filter = String::new();
+        let max_entries = 0;
+        let max_age_days = 0;
+        let current = None;
+        let last_position = None;
+        Self {
+            history_path,
+            entries,
+            history_state,
+            filter,
+            max_entries,
+            max_age_days,
+            current,
+            last_position,
+        }
+    }
+    pub fn new(max_entries: usize, max_age_days: u64) -> Self {
+        Self {
+            max_entries,
+            max_age_days,
+            ..Self::default()
+        }
+    }
+
+    /// Adds `video` as the newest entry, started now.
+    pub fn record(&mut self, video: Video) {
+        self.entries.push(HistoryEntry {
+            video,
+            played_at: now(),
+            played_for: 0.0,
+        });
+        self.current = Some(self.entries.len() - 1);
+        self.last_position = None;
+        self.prune();
+    }
+    /// Something that doesn't go into the history started playing, so no entry gets its time.
+    pub fn untrack(&mut self) {
+        self.current = None;
+        self.last_position = None;
+    }
+    /// Adds how far mpv's `time-pos` moved on since the last update to how long the current entry
+    /// has played for. Jumps further than the time that passed are seeks and don't count.
+    pub fn update_played_for(&mut self, position: f64) {
+        let now = Instant::now();
+        if let Some((last, at)) = self.last_position.replace((position, now))
+            && let Some(entry) = self.current.and_then(|index| self.entries.get_mut(index))
+        {
+            let played = position - last;
+            // a second of slack for mpv's update interval and faster playback speeds
+            if played > 0.0 && played <= now.duration_since(at).as_secs_f64() + 1.0 {
+                entry.played_for += played;
+            }
+        }
+    }
+    /// Removes the entry at `index` of [`History::visible`].
+    pub fn remove(&mut self, index: usize) -> color_eyre::Result<HistoryEntry> {
+        let index = self.entry_index(index)?;
+        let entry = self.entries[index].clone();
+        self.retain_entries(|entry_index, _| entry_index != index);
+        self.clamp_selection();
+        Ok(entry)
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.current = None;
+        self.history_state.select(Some(0));
+    }
+    /// Drops entries past the configured age, then the oldest ones past the configured count.
+    fn prune(&mut self) {
+        if self.max_age_days > 0 {
+            let cutoff = now().saturating_sub(self.max_age_days * 24 * 60 * 60);
+            self.retain_entries(|_, entry| entry.played_at >= cutoff);
+        }
+        if self.max_entries > 0 && self.entries.len() > self.max_entries {
+            let excess = self.entries.len() - self.max_entries;
+            self.retain_entries(|index, _| index >= excess);
+        }
+        self.clamp_selection();
+    }
+    /// Keeps the entries `keep` is true for, given their index, and follows the current entry to
+    /// where it ends up.
+    fn retain_entries(&mut self, keep: impl Fn(usize, &HistoryEntry) -> bool) {
+        let (mut index, mut kept, mut current) = (0, 0, None);
+        self.entries.retain(|entry| {
+            let keep = keep(index, entry);
+            if keep {
+                if Some(index) == self.current {
+                    current = Some(kept);
+                }
+                kept += 1;
+            }
+            index += 1;
+            keep
+        });
+        self.current = current;
+    }
+
+    /// Entries matching the filter, newest first.
+    pub fn visible(&self) -> Vec<&HistoryEntry> {
+        self.visible_indices()
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .collect()
+    }
+    /// The entry under the cursor.
+    pub fn selected(&self) -> Option<&HistoryEntry> {
+        let index = self.entry_index(self.history_state.selected()?).ok()?;
+        self.entries.get(index)
+    }
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.history_state.select(Some(0));
+    }
+    pub fn history_state(&mut self) -> &mut ListState {
+        &mut self.history_state
+    }
+
+    pub fn retrieve_history(&mut self) -> color_eyre::Result<()> {
+        if fs::exists(&self.history_path)? {
+            let history = fs::read_to_string(&self.history_path)?;
+            self.entries = serde_json::from_str(history.as_str())?;
+            self.prune();
+        }
+        Ok(())
+    }
+    pub fn save_history(&self) -> color_eyre::Result<()> {
+        if let Some((path, _filename)) = self.history_path.rsplit_once("/") {
+            fs::DirBuilder::new().recursive(true).create(path)?;
+        }
+        let history_json = serde_json::to_string_pretty(&self.entries)?;
+        fs::write(self.history_path.clone(), history_json)?;
+        Ok(())
+    }
+
+    fn visible_indices(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        (0..self.entries.len())
+            .rev()
+            .filter(|index| {
+                let video = &self.entries[*index].video;
+                filter.is_empty()
+                    || video.title.to_lowercase().contains(&filter)
+                    || video.uploader.to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+    /// Maps an index of [`History::visible`] onto `entries`.
+    fn entry_index(&self, visible_index: usize) -> color_eyre::Result<usize> {
+        self.visible_indices()
+            .get(visible_index)
+            .copied()
+            .ok_or_else(|| {
+                color_eyre::eyre::eyre!("Index out of bounds in {} at {} ", file!(), line!())
+            })
+    }
+    fn clamp_selection(&mut self) {
+        let last = self.visible_indices().len().saturating_sub(1);
+        if self
+            .history_state
+            .selected()
+            .is_some_and(|selected| selected > last)
+        {
+            self.history_state.select(Some(last));
+        }
+    }
+    fn init_history_path() -> String {
+        match dirs::data_local_dir() {
+            Some(mut path) => {
+                path.push("ymp");
+                path.push("history.json");
+                path.to_string_lossy().into_owned()
+            }
+            None => {
+                // TODO Add error handling for history path not accessible.
+                String::from("Placeholder")
+            }
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str) -> Video {
+        Video {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn played_for(history: &History) -> Vec<f64> {
+        history
+            .entries
+            .iter()
+            .map(|entry| entry.played_for)
+            .collect()
+    }
+
+    #[test]
+    fn adds_played_time_to_the_current_entry_only() {
+        let mut history = History::new(0, 0);
+        history.entries.push(HistoryEntry {
+            video: video("old"),
+            ..Default::default()
+        });
+        // nothing recorded this session yet
+        history.update_played_for(1.0);
+        history.update_played_for(1.5);
+        assert_eq!(played_for(&history), [0.0]);
+        history.record(video("a"));
+        history.update_played_for(10.0);
+        history.update_played_for(10.5);
+        // a seek forward doesn't count, going back neither
+        history.update_played_for(100.0);
+        history.update_played_for(50.0);
+        history.update_played_for(50.5);
+        assert_eq!(played_for(&history), [0.0, 1.0]);
+        history.untrack();
+        history.update_played_for(1.0);
+        history.update_played_for(1.5);
+        assert_eq!(played_for(&history), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn follows_the_current_entry_through_removals() {
+        let mut history = History::new(2, 0);
+        history.record(video("a"));
+        history.record(video("b"));
+        history.record(video("c"));
+        assert_eq!(history.entries.len(), 2);
+        history.update_played_for(0.0);
+        history.update_played_for(0.5);
+        // the newest entry is shown first
+        history.remove(1).unwrap();
+        history.update_played_for(1.0);
+        assert_eq!(played_for(&history), [1.0]);
+        assert_eq!(history.entries[0].video.id, "c");
+        // once the current entry is gone nothing else picks up its time
+        history.remove(0).unwrap();
+        history.update_played_for(1.5);
+        history.record(video("d"));
+        history.clear();
+        history.update_played_for(2.0);
+        history.update_played_for(2.5);
+        history.record(video("e"));
+        assert_eq!(played_for(&history), [0.0]);
+    }
+}
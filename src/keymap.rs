@@ -0,0 +1,639 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Everything a key can be bound to. What an action does can depend on the screen, e.g.
+/// [`Action::Confirm`] plays a queue entry but loads a playlist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    NextTab,
+    PreviousTab,
+    SelectNext,
+    SelectPrevious,
+    SelectFirst,
+    SelectLast,
+    /// Enter: play, load or pick whatever is under the cursor.
+    Confirm,
+    Cancel,
+    Search,
+    SwitchPlaybackMode,
+    CycleRepeat,
+    ToggleShuffle,
+    ToggleGapless,
+    PlayPause,
+    Stop,
+    VolumeUp,
+    VolumeDown,
+    SeekForward,
+    SeekBackward,
+    SeekStart,
+    /// Seek to this percentage of the track.
+    SeekPercent(u8),
+    SeekPrompt,
+    AddToPlaylist,
+    AppendToQueue,
+    PlayNext,
+    Remove,
+    Clear,
+    MoveDown,
+    MoveUp,
+    RemoveDuplicates,
+    Undo,
+    Import,
+    Export,
+    NewPlaylist,
+    RenamePlaylist,
+    Filter,
+    CycleTheme,
+    /// Takes down the notifications on screen.
+    DismissNotifications,
+    /// Queues every result, loading the rest of them too.
+    EnqueueAll,
+}
+
+impl Action {
+    /// Actions by the name they have in the `keybindings` setting.
+    const NAMES: [(&str, Action); 47] = [
+        ("quit", Action::Quit),
+        ("next_tab", Action::NextTab),
+        ("previous_tab", Action::PreviousTab),
+        ("select_next", Action::SelectNext),
+        ("select_previous", Action::SelectPrevious),
+        ("select_first", Action::SelectFirst),
+        ("select_last", Action::SelectLast),
+        ("confirm", Action::Confirm),
+        ("cancel", Action::Cancel),
+        ("search", Action::Search),
+        ("switch_playback_mode", Action::SwitchPlaybackMode),
+        ("cycle_repeat", Action::CycleRepeat),
+        ("toggle_shuffle", Action::ToggleShuffle),
+        ("toggle_gapless", Action::ToggleGapless),
+        ("play_pause", Action::PlayPause),
+        ("stop", Action::Stop),
+        ("volume_up", Action::VolumeUp),
+        ("volume_down", Action::VolumeDown),
+        ("seek_forward", Action::SeekForward),
+        ("seek_backward", Action::SeekBackward),
+        ("seek_start", Action::SeekStart),
+        ("seek_10_percent", Action::SeekPercent(10)),
+        ("seek_20_percent", Action::SeekPercent(20)),
+        ("seek_30_percent", Action::SeekPercent(30)),
+        ("seek_40_percent", Action::SeekPercent(40)),
+        ("seek_50_percent", Action::SeekPercent(50)),
+        ("seek_60_percent", Action::SeekPercent(60)),
+        ("seek_70_percent", Action::SeekPercent(70)),
+        ("seek_80_percent", Action::SeekPercent(80)),
+        ("seek_prompt", Action::SeekPrompt),
+        ("add_to_playlist", Action::AddToPlaylist),
+        ("append_to_queue", Action::AppendToQueue),
+        ("play_next", Action::PlayNext),
+        ("remove", Action::Remove),
+        ("clear", Action::Clear),
+        ("move_down", Action::MoveDown),
+        ("move_up", Action::MoveUp),
+        ("remove_duplicates", Action::RemoveDuplicates),
+        ("undo", Action::Undo),
+        ("import", Action::Import),
+        ("export", Action::Export),
+        ("new_playlist", Action::NewPlaylist),
+        ("rename_playlist", Action::RenamePlaylist),
+        ("filter", Action::Filter),
+        ("cycle_theme", Action::CycleTheme),
+        ("dismiss_notifications", Action::DismissNotifications),
+        ("enqueue_all", Action::EnqueueAll),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Self::NAMES
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, action)| action == self)
+            .map_or("unknown", |(name, _)| name)
+    }
+}
+
+/// Where a binding applies. Screen bindings are looked up before [`KeyContext::Global`] ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    /// Every screen, unless the screen binds the same keys itself.
+    Global,
+    Queue,
+    Results,
+    Playlists,
+    History,
+    Log,
+    /// The "Add to Playlist" picker.
+    Picker,
+}
+
+impl KeyContext {
+    const ALL: [(&str, KeyContext); 7] = [
+        ("global", KeyContext::Global),
+        ("queue", KeyContext::Queue),
+        ("results", KeyContext::Results),
+        ("playlists", KeyContext::Playlists),
+        ("history", KeyContext::History),
+        ("log", KeyContext::Log),
+        ("picker", KeyContext::Picker),
+    ];
+
+    fn from_name(name: &str) -> Option<KeyContext> {
+        Self::ALL
+            .iter()
+            .find(|(context_name, _)| *context_name == name)
+            .map(|(_, context)| *context)
+    }
+
+    fn name(&self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, context)| context == self)
+            .map_or("unknown", |(name, _)| name)
+    }
+}
+
+/// The bindings ymp ships with, as `(context, action, keys)`.
+const DEFAULT_BINDINGS: &[(KeyContext, Action, &[&str])] = &[
+    (
+        KeyContext::Global,
+        Action::Quit,
+        &["q", "Q", "ctrl+c", "ctrl+C"],
+    ),
+    (KeyContext::Global, Action::PreviousTab, &["H"]),
+    (KeyContext::Global, Action::NextTab, &["L"]),
+    (KeyContext::Global, Action::SelectNext, &["j"]),
+    (KeyContext::Global, Action::SelectPrevious, &["k"]),
+    (KeyContext::Global, Action::Search, &["/"]),
+    (KeyContext::Global, Action::SwitchPlaybackMode, &["m"]),
+    (KeyContext::Global, Action::CycleTheme, &["T"]),
+    (KeyContext::Global, Action::DismissNotifications, &["x"]),
+    (KeyContext::Global, Action::Cancel, &["esc"]),
+    (KeyContext::Queue, Action::Confirm, &["enter"]),
+    (KeyContext::Queue, Action::Clear, &["C"]),
+    (KeyContext::Queue, Action::Remove, &["d"]),
+    (KeyContext::Queue, Action::Import, &["I"]),
+    (KeyContext::Queue, Action::Export, &["E"]),
+    (KeyContext::Queue, Action::AddToPlaylist, &["a"]),
+    (KeyContext::Queue, Action::MoveDown, &["J"]),
+    (KeyContext::Queue, Action::MoveUp, &["K"]),
+    (KeyContext::Queue, Action::PlayNext, &["n"]),
+    (KeyContext::Queue, Action::RemoveDuplicates, &["D"]),
+    (KeyContext::Queue, Action::Undo, &["u"]),
+    (KeyContext::Queue, Action::CycleRepeat, &["r"]),
+    (KeyContext::Queue, Action::ToggleShuffle, &["z"]),
+    (KeyContext::Queue, Action::ToggleGapless, &["P"]),
+    (KeyContext::Queue, Action::Stop, &["s"]),
+    (KeyContext::Queue, Action::VolumeDown, &["9"]),
+    (KeyContext::Queue, Action::VolumeUp, &["0"]),
+    (KeyContext::Queue, Action::PlayPause, &["space"]),
+    (KeyContext::Queue, Action::SeekBackward, &["left"]),
+    (KeyContext::Queue, Action::SeekForward, &["right"]),
+    (KeyContext::Queue, Action::SeekStart, &["home"]),
+    (KeyContext::Queue, Action::SeekPercent(10), &["1"]),
+    (KeyContext::Queue, Action::SeekPercent(20), &["2"]),
+    (KeyContext::Queue, Action::SeekPercent(30), &["3"]),
+    (KeyContext::Queue, Action::SeekPercent(40), &["4"]),
+    (KeyContext::Queue, Action::SeekPercent(50), &["5"]),
+    (KeyContext::Queue, Action::SeekPercent(60), &["6"]),
+    (KeyContext::Queue, Action::SeekPercent(70), &["7"]),
+    (KeyContext::Queue, Action::SeekPercent(80), &["8"]),
+    (KeyContext::Queue, Action::SeekPrompt, &["g"]),
+    (KeyContext::Results, Action::Confirm, &["enter"]),
+    (KeyContext::Results, Action::AddToPlaylist, &["a"]),
+    (KeyContext::Results, Action::PlayNext, &["n"]),
+    (KeyContext::Results, Action::EnqueueAll, &["A"]),
+    (KeyContext::Results, Action::CycleRepeat, &["r"]),
+    (KeyContext::Results, Action::ToggleShuffle, &["z"]),
+    (KeyContext::Results, Action::ToggleGapless, &["P"]),
+    (KeyContext::Playlists, Action::Confirm, &["enter"]),
+    (KeyContext::Playlists, Action::AppendToQueue, &["a"]),
+    (KeyContext::Playlists, Action::NewPlaylist, &["N"]),
+    (KeyContext::Playlists, Action::RenamePlaylist, &["R"]),
+    (KeyContext::Playlists, Action::Remove, &["d"]),
+    (KeyContext::Playlists, Action::Import, &["I"]),
+    (KeyContext::Playlists, Action::Export, &["E"]),
+    (KeyContext::History, Action::Confirm, &["enter"]),
+    (KeyContext::History, Action::AppendToQueue, &["a"]),
+    (KeyContext::History, Action::Filter, &["f"]),
+    (KeyContext::History, Action::Remove, &["d"]),
+    (KeyContext::History, Action::Clear, &["C"]),
+    (KeyContext::Log, Action::Clear, &["C"]),
+    (KeyContext::Picker, Action::Quit, &["ctrl+c", "ctrl+C"]),
+    (KeyContext::Picker, Action::SelectNext, &["j", "down"]),
+    (KeyContext::Picker, Action::SelectPrevious, &["k", "up"]),
+    (KeyContext::Picker, Action::Confirm, &["enter"]),
+    (KeyContext::Picker, Action::Cancel, &["esc", "q"]),
+];
+
+/// A single key press, with its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn from_event(key: KeyEvent) -> Self {
+        Self::normalized(key.code, key.modifiers)
+    }
+
+    /// Shift is already part of a character's case, and terminals don't agree on reporting it,
+    /// so it's dropped for characters.
+    fn normalized(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        if let KeyCode::Char(_) = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Parses a chord like `j`, `G`, `enter`, `ctrl+c` or `alt+left`.
+    fn parse(chord: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = chord.split('+').collect();
+        // "+" on its own, or as the key after a modifier
+        if chord == "+" || chord.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let key = parts.pop()?;
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(ch), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(ch.to_ascii_uppercase())
+            }
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        Some(Self::normalized(code, modifiers))
+    }
+}
+
+/// Parses a binding: chords separated by spaces, e.g. `g g` or `ctrl+x ctrl+s`. A run of plain
+/// characters that isn't a key name is a sequence too, so `gg` is the same as `g g`.
+fn parse_sequence(keys: &str) -> Option<Vec<KeyChord>> {
+    let mut sequence = Vec::new();
+    for chord in keys.split_whitespace() {
+        match KeyChord::parse(chord) {
+            Some(chord) => sequence.push(chord),
+            None if !chord.contains('+') => {
+                for ch in chord.chars() {
+                    sequence.push(KeyChord::parse(&ch.to_string())?);
+                }
+            }
+            None => return None,
+        }
+    }
+    (!sequence.is_empty()).then_some(sequence)
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    sequence: Vec<KeyChord>,
+    /// The keys as written, for error messages.
+    keys: String,
+    action: Action,
+}
+
+/// Key bindings for every [`KeyContext`], with the defaults overridden by the `keybindings` setting.
+#[derive(Debug, Default)]
+pub struct Keymap {
+    /// Bindings in effect per context, global ones already folded into the screens.
+    bindings: HashMap<KeyContext, Vec<Binding>>,
+    /// Keys typed so far of a sequence that isn't complete yet.
+    pending: Vec<KeyChord>,
+    /// Context the pending keys were typed in.
+    pending_context: Option<KeyContext>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults and `overrides`, a map of context to action to keys.
+    /// An action listed in `overrides` loses its default keys in that context, so `[]` unbinds it.
+    pub fn new(
+        overrides: &BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    ) -> color_eyre::Result<Self> {
+        let mut configured: Vec<(KeyContext, Action, Vec<String>)> = DEFAULT_BINDINGS
+            .iter()
+            .map(|(context, action, keys)| {
+                let keys = keys.iter().map(|key| key.to_string()).collect();
+                (*context, *action, keys)
+            })
+            .collect();
+        for (context_name, actions) in overrides {
+            let context = KeyContext::from_name(context_name).ok_or_else(|| {
+                color_eyre::eyre::eyre!("Unknown keybindings context {context_name}")
+            })?;
+            for (action_name, keys) in actions {
+                let action = Action::from_name(action_name).ok_or_else(|| {
+                    color_eyre::eyre::eyre!(
+                        "Unknown action {action_name} in keybindings.{context_name}"
+                    )
+                })?;
+                configured.retain(|(c, a, _)| !(*c == context && *a == action));
+                configured.push((context, action, keys.clone()));
+            }
+        }
+
+        let mut own: HashMap<KeyContext, Vec<Binding>> = HashMap::new();
+        for (context, action, keys) in configured {
+            for key in keys {
+                let sequence = parse_sequence(&key).ok_or_else(|| {
+                    color_eyre::eyre::eyre!(
+                        "Invalid key {key:?} for {} in keybindings.{}",
+                        action.name(),
+                        context.name()
+                    )
+                })?;
+                own.entry(context).or_default().push(Binding {
+                    sequence,
+                    keys: key,
+                    action,
+                });
+            }
+        }
+
+        let global = own.get(&KeyContext::Global).cloned().unwrap_or_default();
+        let mut bindings = HashMap::new();
+        for (_, context) in KeyContext::ALL {
+            let mut effective = own.get(&context).cloned().unwrap_or_default();
+            if context != KeyContext::Global && context != KeyContext::Picker {
+                // a screen binding the same keys as a global binding takes its place
+                for binding in &global {
+                    if !effective
+                        .iter()
+                        .any(|screen| screen.sequence == binding.sequence)
+                    {
+                        effective.push(binding.clone());
+                    }
+                }
+            }
+            Self::check_conflicts(context, &effective)?;
+            bindings.insert(context, effective);
+        }
+        let pending = Vec::new();
+        let pending_context = None;
+        Ok(Self {
+            bindings,
+            pending,
+            pending_context,
+        })
+    }
+
+    /// Forgets a half-typed sequence, e.g. when a text prompt takes the keys over.
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Feeds a key press through the keymap. Returns the action once a binding is complete; keys
+    /// that start a longer sequence are held on to until the rest is typed.
+    pub fn handle(&mut self, context: KeyContext, key: KeyEvent) -> Option<Action> {
+        // a sequence started on another screen or in the picker doesn't carry over
+        if self.pending_context != Some(context) {
+            self.pending.clear();
+            self.pending_context = Some(context);
+        }
+        self.pending.push(KeyChord::from_event(key));
+        let Some(bindings) = self.bindings.get(&context) else {
+            self.pending.clear();
+            return None;
+        };
+        let matches: Vec<&Binding> = bindings
+            .iter()
+            .filter(|binding| binding.sequence.starts_with(&self.pending))
+            .collect();
+        if let Some(binding) = matches
+            .iter()
+            .find(|binding| binding.sequence == self.pending)
+        {
+            self.pending.clear();
+            return Some(binding.action);
+        }
+        if !matches.is_empty() {
+            return None;
+        }
+        // the sequence went nowhere; the last key may still start something on its own
+        let retry = self.pending.len() > 1;
+        self.pending.clear();
+        if retry {
+            return self.handle(context, key);
+        }
+        None
+    }
+
+    /// Two bindings conflict if they have the same keys, or one's keys start the other's, since
+    /// the longer one could then never be typed.
+    fn check_conflicts(context: KeyContext, bindings: &[Binding]) -> color_eyre::Result<()> {
+        for (index, first) in bindings.iter().enumerate() {
+            for second in &bindings[index + 1..] {
+                if first.sequence == second.sequence && first.action == second.action {
+                    continue;
+                }
+                let (shorter, longer) = if first.sequence.len() <= second.sequence.len() {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                if longer.sequence.starts_with(&shorter.sequence) {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Key binding conflict in keybindings.{}: {:?} ({}) and {:?} ({})",
+                        context.name(),
+                        shorter.keys,
+                        shorter.action.name(),
+                        longer.keys,
+                        longer.action.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn press(ch: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)
+    }
+
+    fn keymap(overrides: &[(&str, &str, &[&str])]) -> color_eyre::Result<Keymap> {
+        let mut map: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+        for (context, action, keys) in overrides {
+            map.entry(context.to_string()).or_default().insert(
+                action.to_string(),
+                keys.iter().map(|key| key.to_string()).collect(),
+            );
+        }
+        Keymap::new(&map)
+    }
+
+    #[test]
+    fn parses_chords() {
+        let plus = KeyCode::Char('+');
+        assert_eq!(KeyChord::parse("+"), Some(chord(plus, KeyModifiers::NONE)));
+        assert_eq!(
+            KeyChord::parse("ctrl++"),
+            Some(chord(plus, KeyModifiers::CONTROL))
+        );
+        assert_eq!(KeyChord::parse("shift+x"), KeyChord::parse("X"));
+        assert_eq!(
+            KeyChord::parse("X"),
+            Some(chord(KeyCode::Char('X'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyChord::from_event(KeyEvent::new(KeyCode::Char('X'), KeyModifiers::SHIFT)),
+            KeyChord::parse("X").unwrap()
+        );
+        assert_ne!(KeyChord::parse("ctrl+c"), KeyChord::parse("ctrl+C"));
+        assert_eq!(
+            KeyChord::parse("f5"),
+            Some(chord(KeyCode::F(5), KeyModifiers::NONE))
+        );
+        assert_eq!(KeyChord::parse("F5"), KeyChord::parse("f5"));
+        assert_eq!(
+            KeyChord::parse("Alt+Left"),
+            Some(chord(KeyCode::Left, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            KeyChord::parse("f"),
+            Some(chord(KeyCode::Char('f'), KeyModifiers::NONE))
+        );
+        for chord in ["", "fx", "hyper+x", "ctrl+", "enterr"] {
+            assert_eq!(KeyChord::parse(chord), None, "{chord}");
+        }
+    }
+
+    #[test]
+    fn parses_sequences() {
+        let g = KeyChord::parse("g").unwrap();
+        assert_eq!(parse_sequence("gg"), Some(vec![g, g]));
+        assert_eq!(parse_sequence(" g  g "), Some(vec![g, g]));
+        assert_eq!(
+            parse_sequence("ctrl+x ctrl+s"),
+            Some(vec![
+                chord(KeyCode::Char('x'), KeyModifiers::CONTROL),
+                chord(KeyCode::Char('s'), KeyModifiers::CONTROL),
+            ])
+        );
+        assert_eq!(
+            parse_sequence("enter"),
+            Some(vec![chord(KeyCode::Enter, KeyModifiers::NONE)])
+        );
+        assert_eq!(parse_sequence(""), None);
+        assert_eq!(parse_sequence("ctrl+xy"), None);
+    }
+
+    #[test]
+    fn catches_prefix_conflicts() {
+        // the queue's own "g" would swallow the first key of a global "gg"
+        let error = keymap(&[("global", "select_first", &["gg"])]).unwrap_err();
+        assert!(error.to_string().contains("keybindings.queue"), "{error}");
+        let error = keymap(&[("history", "filter", &["j j"])]).unwrap_err();
+        assert!(error.to_string().contains("keybindings.history"), "{error}");
+        assert!(keymap(&[("results", "cancel", &["x y"])]).is_err());
+        assert!(keymap(&[("results", "cancel", &["x"])]).is_ok());
+        // a screen binding the same keys as a global one replaces it
+        assert!(keymap(&[("queue", "remove", &["j"])]).is_ok());
+        assert!(
+            keymap(&[
+                ("global", "select_first", &["gg"]),
+                ("queue", "seek_prompt", &["ctrl+g"]),
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn handles_sequences_and_falls_back() {
+        let mut keymap = keymap(&[
+            ("global", "select_first", &["g g"]),
+            ("queue", "seek_prompt", &["ctrl+g"]),
+        ])
+        .unwrap();
+        let history = KeyContext::History;
+        assert_eq!(keymap.handle(history, press('j')), Some(Action::SelectNext));
+        assert_eq!(keymap.handle(history, press('g')), None);
+        assert_eq!(
+            keymap.handle(history, press('g')),
+            Some(Action::SelectFirst)
+        );
+        // a key that doesn't continue the sequence still does its own thing
+        assert_eq!(keymap.handle(history, press('g')), None);
+        assert_eq!(keymap.handle(history, press('j')), Some(Action::SelectNext));
+        assert_eq!(keymap.handle(history, press('g')), None);
+        assert_eq!(keymap.handle(history, press('?')), None);
+        assert_eq!(
+            keymap.handle(history, press('k')),
+            Some(Action::SelectPrevious)
+        );
+        // screen bindings win over global ones
+        assert_eq!(
+            keymap.handle(KeyContext::Queue, press('d')),
+            Some(Action::Remove)
+        );
+        assert_eq!(
+            keymap.handle(KeyContext::Playlists, press('d')),
+            Some(Action::Remove)
+        );
+        assert_eq!(keymap.handle(KeyContext::Log, press('d')), None);
+    }
+
+    #[test]
+    fn drops_pending_keys_on_a_new_context() {
+        let mut keymap = keymap(&[
+            ("global", "select_first", &["g g"]),
+            ("queue", "seek_prompt", &["ctrl+g"]),
+        ])
+        .unwrap();
+        assert_eq!(keymap.handle(KeyContext::Results, press('g')), None);
+        assert_eq!(keymap.handle(KeyContext::History, press('g')), None);
+        assert_eq!(
+            keymap.handle(KeyContext::History, press('g')),
+            Some(Action::SelectFirst)
+        );
+        assert_eq!(keymap.handle(KeyContext::History, press('g')), None);
+        keymap.clear_pending();
+        assert_eq!(keymap.handle(KeyContext::History, press('g')), None);
+        assert_eq!(
+            keymap.handle(KeyContext::Picker, press('q')),
+            Some(Action::Cancel)
+        );
+    }
+}
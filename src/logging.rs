@@ -0,0 +1,143 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Size the log file grows to before it's rotated.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept next to the current one, `ymp.log.1` being the newest.
+const KEPT_LOGS: usize = 3;
+
+/// Writes log records to `ymp.log`, moving it aside as `ymp.log.1` (and so on) once it gets big.
+struct FileLogger {
+    level: LevelFilter,
+    path: PathBuf,
+    /// The open log file and how many bytes it holds.
+    file: Mutex<(File, u64)>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // dependencies only get to add their warnings and errors
+        metadata.level() <= self.level
+            && (metadata.target().starts_with("ymp") || metadata.level() <= Level::Warn)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            format_utc(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if file.1 + line.len() as u64 > MAX_LOG_BYTES
+            && let Ok(rotated) = rotate(&self.path)
+        {
+            *file = (rotated, 0);
+        }
+        // nowhere left to report a failing log file, so it's dropped
+        if file.0.write_all(line.as_bytes()).is_ok() {
+            file.1 += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.0.flush();
+        }
+    }
+}
+
+/// Starts logging at `level` to `ymp.log` in the state dir (`~/.local/state/ymp/`), or the data dir
+/// where there is none. Does nothing for [`LevelFilter::Off`].
+pub fn init(level: LevelFilter) -> color_eyre::Result<()> {
+    if level == LevelFilter::Off {
+        return Ok(());
+    }
+    let path = log_path()
+        .ok_or_else(|| color_eyre::eyre::eyre!("Could not find a directory for the log file"))?;
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .create(dir)
+            .map_err(|e| color_eyre::eyre::eyre!("Could not create {}: {e}", dir.display()))?;
+    }
+    let open_error = |e| color_eyre::eyre::eyre!("Could not open log file {}: {e}", path.display());
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(open_error)?;
+    let mut size = file.metadata()?.len();
+    if size > MAX_LOG_BYTES {
+        file = rotate(&path).map_err(open_error)?;
+        size = 0;
+    }
+    log::set_boxed_logger(Box::new(FileLogger {
+        level,
+        path,
+        file: Mutex::new((file, size)),
+    }))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+fn log_path() -> Option<PathBuf> {
+    let mut path = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    path.push("ymp");
+    path.push("ymp.log");
+    Some(path)
+}
+
+/// Shifts `ymp.log.1` to `ymp.log.2` and so on, dropping the oldest, moves the current log to
+/// `ymp.log.1` and opens a fresh one.
+fn rotate(path: &Path) -> std::io::Result<File> {
+    let rotated = |index: usize| PathBuf::from(format!("{}.{index}", path.display()));
+    for index in (1..KEPT_LOGS).rev() {
+        if fs::exists(rotated(index))? {
+            fs::rename(rotated(index), rotated(index + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))?;
+    File::create(path)
+}
+
+/// `time` as `2024-01-31T12:34:56.789Z`.
+fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+    // days since 1970-01-01 to a civil date, see https://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
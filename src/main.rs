@@ -1,16 +1,51 @@
 mod app;
+mod cli;
+mod history;
+mod keymap;
+mod logging;
+mod notifications;
 mod player;
+mod playlist_files;
+mod playlists;
 mod queue;
 mod search;
+mod search_filters;
 mod settings;
+mod theme;
 mod types;
 mod ui;
 use crate::app::App;
+use crate::cli::Cli;
+use crate::keymap::Keymap;
+use crate::settings::Settings;
+use crate::theme::Theme;
+use std::env;
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
+    let cli = Cli::parse(env::args().skip(1))?;
+    if let Some(command) = cli.command {
+        // one-off work like import/export runs without the TUI
+        return command.run();
+    }
+    // a broken settings file is reported before the terminal is taken over
+    let settings = Settings::load(cli.config, &cli.settings)?;
+    let logging = logging::init(settings.log_level());
+    log::info!("ymp {} starting", env!("CARGO_PKG_VERSION"));
+    let keymap = Keymap::new(settings.keybindings())?;
+    let theme = Theme::new(settings.theme(), settings.colors())?;
+    let mut app = App::new(settings, keymap, theme);
+    // ymp works just as well without a log file
+    if let Err(e) = logging {
+        app.notifications
+            .warn(format!("Could not start logging: {e:#}"));
+    }
     let terminal = ratatui::init();
-    let result = App::new().run(terminal).await;
+    let result = app.run(terminal, cli.url).await;
     ratatui::restore();
+    match &result {
+        Ok(()) => log::info!("ymp quit"),
+        Err(e) => log::error!("ymp quit with an error: {e:#}"),
+    }
     result
 }
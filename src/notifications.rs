@@ -0,0 +1,152 @@
+use crate::history;
+use crate::settings::Settings;
+use ratatui::widgets::ListState;
+use std::time::{Duration, Instant};
+
+/// Most entries kept in the log, oldest ones go first.
+const MAX_LOG_ENTRIES: usize = 500;
+
+/// How serious a [`Notification`] is. Warnings and errors also go into the log.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    /// How many times in a row it came up.
+    pub count: usize,
+    /// Unix timestamp (seconds) of the last time it came up.
+    pub raised_at: u64,
+    /// When its toast went up, to take it down again.
+    shown_at: Instant,
+}
+
+/// Toasts shown on top of the UI for a few seconds, and a log of past warnings and errors.
+#[derive(Debug)]
+pub struct Notifications {
+    /// Toasts still up, oldest first.
+    toasts: Vec<Notification>,
+    /// Past warnings and errors, newest first.
+    log: Vec<Notification>,
+    log_state: ListState,
+    /// How long info toasts stay up.
+    info_duration: Duration,
+    /// How long warning and error toasts stay up.
+    error_duration: Duration,
+}
+
+impl Notifications {
+    pub fn default() -> Self {
+        let toasts = Vec::new();
+        let log = Vec::new();
+        let log_state = ListState::default().with_selected(Some(0));
+        let info_duration = Duration::from_secs(2);
+        let error_duration = Duration::from_secs(6);
+        Self {
+            toasts,
+            log,
+            log_state,
+            info_duration,
+            error_duration,
+        }
+    }
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            info_duration: settings.status_message_duration(),
+            error_duration: settings.error_message_duration(),
+            ..Self::default()
+        }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Severity::Info, message.into());
+    }
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.push(Severity::Warning, message.into());
+    }
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Severity::Error, message.into());
+    }
+    /// Shows `message` as a toast. The same message coming up again bumps its count instead of
+    /// stacking another toast, so an error that repeats doesn't flood the screen or the log.
+    pub fn push(&mut self, severity: Severity, message: String) {
+        match severity {
+            Severity::Info => log::debug!("{message}"),
+            Severity::Warning => log::warn!("{message}"),
+            Severity::Error => log::error!("{message}"),
+        }
+        let notification = Notification {
+            severity,
+            message,
+            count: 1,
+            raised_at: history::now(),
+            shown_at: Instant::now(),
+        };
+        if severity != Severity::Info {
+            match self.log.first_mut() {
+                Some(last) if Self::repeats(last, &notification) => {
+                    last.count += 1;
+                    last.raised_at = notification.raised_at;
+                }
+                _ => {
+                    self.log.insert(0, notification.clone());
+                    self.log.truncate(MAX_LOG_ENTRIES);
+                }
+            }
+        }
+        match self.toasts.last_mut() {
+            Some(last) if Self::repeats(last, &notification) => {
+                last.count += 1;
+                last.shown_at = notification.shown_at;
+            }
+            _ => self.toasts.push(notification),
+        }
+    }
+    fn repeats(last: &Notification, notification: &Notification) -> bool {
+        last.severity == notification.severity && last.message == notification.message
+    }
+
+    /// Toasts that are still up, oldest first.
+    pub fn toasts(&mut self) -> &[Notification] {
+        let (info_duration, error_duration) = (self.info_duration, self.error_duration);
+        self.toasts.retain(|toast| {
+            let duration = match toast.severity {
+                Severity::Info => info_duration,
+                Severity::Warning | Severity::Error => error_duration,
+            };
+            toast.shown_at.elapsed() < duration
+        });
+        &self.toasts
+    }
+    /// Takes down every toast. They stay in the log.
+    pub fn dismiss(&mut self) {
+        self.toasts.clear();
+    }
+
+    pub fn log(&self) -> &[Notification] {
+        &self.log
+    }
+    pub fn log_state(&mut self) -> &mut ListState {
+        &mut self.log_state
+    }
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+        self.log_state.select(Some(0));
+    }
+}
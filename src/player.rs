@@ -1,12 +1,125 @@
 use std::{
-    fs,
-    io::{ErrorKind, Write},
-    os::unix::net::UnixStream,
-    process::{Child, Command, Stdio},
+    collections::HashMap,
+    env, fmt, fs,
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::{fs::DirBuilderExt, fs::PermissionsExt, net::UnixStream},
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::queue::Queue;
-use crate::types::{PlaybackMode, Video};
+use serde_json::{Value, json};
+
+use crate::settings::{Cookies, MpvModeSettings, Settings};
+use crate::types::{PlaybackMode, RepeatMode, Video, format_timestamp, video_id_from_url};
+
+/// Typed replies and events read back from the mpv IPC socket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MpvEvent {
+    Volume(f64),
+    TimePos(Option<f64>),
+    Duration(Option<f64>),
+    Pause(bool),
+    /// mpv finished playing a file. Holds the `reason` mpv reported, e.g. "eof" or "stop".
+    EndFile(String),
+    /// mpv's position in its own playlist changed. `None` once mpv runs out of entries and goes idle.
+    PlaylistPos(Option<usize>),
+    /// The mpv process exited on its own. Holds the last `end-file` reason, if mpv reported one over IPC.
+    Exited(Option<String>),
+    Error(String),
+}
+
+/// What a `request_id` sent to mpv was asking for, so its reply can be turned into an [`MpvEvent`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MpvRequest {
+    Command,
+    Volume,
+}
+
+/// Where to seek to, mapped onto the flags of mpv's `seek` command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeekTarget {
+    /// Seconds forward (positive) or backward (negative) from the current position.
+    Relative(f64),
+    /// Seconds from the start of the track.
+    Absolute(f64),
+    /// Percentage of the track's duration, 0 to 100.
+    Percent(f64),
+}
+
+impl SeekTarget {
+    /// Parses what the user typed into the seek prompt: `50%`, `1:23`, `1:02:03` or plain seconds.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Some(percent) = input.strip_suffix('%') {
+            let percent = percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())?;
+            return (0.0..=100.0)
+                .contains(&percent)
+                .then_some(SeekTarget::Percent(percent));
+        }
+        let parts: Vec<&str> = input.split(':').collect();
+        if parts.len() > 3 {
+            return None;
+        }
+        let mut seconds = 0.0;
+        for part in parts {
+            let value = part.parse::<f64>().ok().filter(|value| value.is_finite())?;
+            if value < 0.0 {
+                return None;
+            }
+            seconds = seconds * 60.0 + value;
+        }
+        Some(SeekTarget::Absolute(seconds))
+    }
+
+    fn mpv_flag(&self) -> &'static str {
+        match self {
+            SeekTarget::Relative(_) => "relative",
+            SeekTarget::Absolute(_) => "absolute",
+            SeekTarget::Percent(_) => "absolute-percent",
+        }
+    }
+
+    fn value(&self) -> f64 {
+        match self {
+            SeekTarget::Relative(value)
+            | SeekTarget::Absolute(value)
+            | SeekTarget::Percent(value) => *value,
+        }
+    }
+}
+
+impl fmt::Display for SeekTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeekTarget::Relative(seconds) if *seconds < 0.0 => write!(f, "-{}s", -seconds),
+            SeekTarget::Relative(seconds) => write!(f, "+{seconds}s"),
+            SeekTarget::Absolute(seconds) => write!(f, "{}", format_timestamp(*seconds)),
+            SeekTarget::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// How long [`Player::playlist_sync`] waits for mpv to report where the playing entry ended up.
+/// mpv can coalesce the moves into no change at all, so it may never do.
+const PLAYLIST_SYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where a restarted track picks up again, see [`Player::playback_mode_switch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Resume {
+    position: f64,
+    paused: bool,
+    volume: Option<f64>,
+}
+
+/// Properties observed with `observe_property` as soon as the IPC socket connects.
+/// mpv reports them back as `property-change` events whenever they change.
+const OBSERVED_PROPERTIES: [&str; 4] = ["time-pos", "duration", "pause", "playlist-pos"];
 
 #[derive(Default, Debug)]
 pub struct Player {
@@ -14,8 +127,50 @@ pub struct Player {
     mpv_process: Option<Child>,
     mpv_stream: Option<UnixStream>,
     mpv_connect_attempts: i8,
+    /// IPC socket of this ymp's mpv, unique to the process so several instances don't clash.
+    mpv_socket: PathBuf,
+    // mpv IPC bookkeeping
+    mpv_read_buffer: Vec<u8>,
+    /// Commands the socket didn't take yet, flushed by [`Player::poll_mpv`].
+    mpv_write_buffer: Vec<u8>,
+    mpv_request_id: u64,
+    mpv_pending_requests: HashMap<u64, MpvRequest>,
+    mpv_end_reason: Option<String>,
+    /// Events raised outside of [`Player::poll_mpv`], e.g. errors while stopping mpv, handed out by its next call.
+    mpv_pending_events: Vec<MpvEvent>,
     now_playing: Video,
+    /// What mpv was given for the track playing. Not always a YouTube URL, see [`Player::play_video_url`].
+    now_playing_url: String,
     is_nowplaying: bool,
+    /// Whether the running mpv holds the whole queue as its playlist (gapless mode).
+    is_playlist: bool,
+    /// Entries handed to mpv with `loadfile` once the IPC socket connects, and the index to start at.
+    playlist_pending: Option<(Vec<Video>, usize)>,
+    playlist_repeat_mode: RepeatMode,
+    /// Playlist position ymp itself asked mpv to jump to, so it isn't mistaken for mpv advancing.
+    playlist_expected_pos: Option<usize>,
+    /// Playlist position mpv last reported.
+    playlist_pos: Option<usize>,
+    /// Where [`Player::playlist_sync`] moves the playing entry, and when. Positions reported on
+    /// the way there are only mpv renumbering its entries.
+    playlist_sync_pos: Option<(usize, Instant)>,
+    volume: Option<f64>,
+    time_pos: Option<f64>,
+    duration: Option<f64>,
+    is_paused: bool,
+    /// Applied to the next mpv started, to carry on from where the last one was.
+    resume: Option<Resume>,
+    /// mpv was started with `--start`, which has to be unset again before it applies to the next entry.
+    reset_start: bool,
+    volume_step: f64,
+    cookies: Option<Cookies>,
+    mpv_path: PathBuf,
+    /// Arguments for every mpv, then for audio and video mode.
+    mpv_args: Vec<String>,
+    mpv_audio: MpvModeSettings,
+    mpv_video: MpvModeSettings,
+    /// Tracks started since the last [`Player::take_started`], for the play history.
+    started: Vec<Video>,
 }
 
 impl Player {
@@ -24,19 +179,81 @@ impl Player {
         let mpv_process: Option<Child> = None;
         let mpv_stream: Option<UnixStream> = None;
         let mpv_connect_attempts = 0;
+        let mpv_socket = Self::init_socket_path();
+        let mpv_read_buffer = Vec::new();
+        let mpv_write_buffer = Vec::new();
+        let mpv_request_id = 0;
+        let mpv_pending_requests = HashMap::new();
+        let mpv_end_reason = None;
+        let mpv_pending_events = Vec::new();
         let now_playing: Video = Video::default();
+        let now_playing_url = String::new();
         let is_nowplaying = false;
+        let is_playlist = false;
+        let playlist_pending = None;
+        let playlist_repeat_mode = RepeatMode::default();
+        let playlist_expected_pos = None;
+        let playlist_pos = None;
+        let playlist_sync_pos = None;
+        let volume = None;
+        let time_pos = None;
+        let duration = None;
+        let is_paused = false;
+        let resume = None;
+        let reset_start = false;
+        let volume_step = 5.0;
+        let cookies = None;
+        let mpv_path = PathBuf::from("mpv");
+        let mpv_args = Vec::new();
+        let mpv_audio = MpvModeSettings::default();
+        let mpv_video = MpvModeSettings::default();
+        let started = Vec::new();
         Self {
             playback_mode,
             mpv_process,
             mpv_stream,
             mpv_connect_attempts,
+            mpv_socket,
+            mpv_read_buffer,
+            mpv_write_buffer,
+            mpv_request_id,
+            mpv_pending_requests,
+            mpv_end_reason,
+            mpv_pending_events,
             now_playing,
+            now_playing_url,
             is_nowplaying,
+            is_playlist,
+            playlist_pending,
+            playlist_repeat_mode,
+            playlist_expected_pos,
+            playlist_pos,
+            playlist_sync_pos,
+            volume,
+            time_pos,
+            duration,
+            is_paused,
+            resume,
+            reset_start,
+            volume_step,
+            cookies,
+            mpv_path,
+            mpv_args,
+            mpv_audio,
+            mpv_video,
+            started,
         }
     }
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(settings: &Settings) -> Self {
+        // Player implements Drop, so its fields are set one by one instead of with `..Self::default()`
+        let mut player = Self::default();
+        player.volume_step = settings.volume_step();
+        player.cookies = settings.cookies();
+        player.mpv_path = settings.mpv_path();
+        player.mpv_args = settings.mpv_args().to_vec();
+        player.mpv_audio = settings.mpv_mode(&PlaybackMode::Audio).clone();
+        player.mpv_video = settings.mpv_mode(&PlaybackMode::Video).clone();
+        player
     }
 
     pub fn now_playing(&self) -> &Video {
@@ -47,20 +264,79 @@ impl Player {
         &self.is_nowplaying
     }
 
+    pub fn set_now_playing(&mut self, video: Video) {
+        self.started.push(video.clone());
+        self.now_playing_url = video.url();
+        self.now_playing = video;
+    }
+
+    /// Drains the tracks started since the last call.
+    pub fn take_started(&mut self) -> Vec<Video> {
+        std::mem::take(&mut self.started)
+    }
+
+    pub fn is_playlist(&self) -> bool {
+        self.is_playlist
+    }
+
+    pub fn volume(&self) -> Option<f64> {
+        self.volume
+    }
+
+    pub fn time_pos(&self) -> Option<f64> {
+        self.time_pos
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
     pub fn playback_mode(&self) -> &PlaybackMode {
         &self.playback_mode
     }
 
-    pub fn playback_mode_switch(&mut self) {
+    /// Switches between audio and video. A track that is playing restarts in the new mode where it
+    /// was, as it was. In gapless mode mpv's playlist is rebuilt from `queue`, at `queue_index`.
+    pub fn playback_mode_switch(
+        &mut self,
+        queue: &[Video],
+        queue_index: Option<usize>,
+    ) -> color_eyre::Result<()> {
         if self.playback_mode == PlaybackMode::Audio {
             self.playback_mode = PlaybackMode::Video;
         } else {
             self.playback_mode = PlaybackMode::Audio;
         }
+        if !self.is_nowplaying {
+            return Ok(());
+        }
+        self.resume = Some(Resume {
+            position: self.time_pos.unwrap_or(0.0),
+            paused: self.is_paused,
+            volume: self.volume,
+        });
+        // the track carries on, so it doesn't go into the history again
+        let started = self.started.len();
+        match queue_index {
+            Some(index) if self.is_playlist => {
+                self.play_playlist(queue, index, self.playlist_repeat_mode)?
+            }
+            _ => {
+                let url = self.now_playing_url.clone();
+                self.kill_mpv();
+                self.spawn_mpv(&url)?;
+            }
+        }
+        self.started.truncate(started);
+        Ok(())
     }
 
     pub fn play_pause(&mut self) -> color_eyre::Result<()> {
-        self.send_mpv_command(vec!["cycle", "pause"])
+        self.send_mpv_command(json!(["cycle", "pause"]), MpvRequest::Command)
     }
 
     pub fn stop(&mut self) -> color_eyre::Result<()> {
@@ -70,153 +346,688 @@ impl Player {
     }
 
     pub fn increase_volume(&mut self) -> color_eyre::Result<()> {
-        self.send_mpv_command(vec!["add", "volume", "5"])
+        self.send_mpv_command(
+            json!(["add", "volume", self.volume_step]),
+            MpvRequest::Command,
+        )
     }
 
     pub fn decrease_volume(&mut self) -> color_eyre::Result<()> {
-        self.send_mpv_command(vec!["add", "volume", "-5"])
+        self.send_mpv_command(
+            json!(["add", "volume", -self.volume_step]),
+            MpvRequest::Command,
+        )
+    }
+
+    pub fn seek(&mut self, target: SeekTarget) -> color_eyre::Result<()> {
+        self.send_mpv_command(
+            json!(["seek", target.value(), target.mpv_flag()]),
+            MpvRequest::Command,
+        )
     }
 
+    /// Asks mpv for the current volume. The answer arrives later through [`Player::poll_mpv`].
     pub fn get_current_volume(&mut self) -> color_eyre::Result<()> {
-        self.send_mpv_command(vec!["get_property", "volume"])
+        self.send_mpv_command(json!(["get_property", "volume"]), MpvRequest::Volume)
     }
 
-    pub fn play_video(&mut self, queue: &mut Queue) -> color_eyre::Result<()> {
+    pub fn play_video(&mut self, video: &Video) -> color_eyre::Result<()> {
         if *self.is_nowplaying() {
             self.kill_mpv();
         }
 
         self.is_nowplaying = true;
+        self.now_playing = video.clone();
+        self.now_playing_url = video.url();
+        self.started.push(video.clone());
+        self.spawn_mpv(&video.url())
+    }
 
-        if let Some(index) = queue.queuelist_state().selected() {
-            if queue.queuelist().len() <= index {
-                return Err(color_eyre::eyre::eyre!(
-                    "Index out of bounds in {} at {} ",
-                    file!(),
-                    line!()
-                ));
-            };
-            self.now_playing = queue.queuelist()[index].clone();
-        }
-
-        match self.playback_mode {
-            PlaybackMode::Audio => {
-                let child = Command::new("mpv")
-                    .arg("--ytdl-format=bestaudio")
-                    .arg(format!(
-                        "https://www.youtube.com/watch?v={}",
-                        self.now_playing.id
-                    ))
-                    .arg("--input-ipc-server=/tmp/mpv-socket")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .stdin(Stdio::null())
-                    .spawn()?;
-                self.mpv_process = Some(child);
-            }
-            PlaybackMode::Video => {
-                let child = Command::new("mpv")
-                    .arg(format!(
-                        "https://www.youtube.com/watch?v={}",
-                        self.now_playing.id
-                    ))
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .spawn()?;
-                self.mpv_process = Some(child);
-            }
+    /// Starts a single long-lived mpv that holds all of `videos` as its own playlist, starting at
+    /// `start`. mpv then moves between entries by itself, which allows gapless and prefetched playback.
+    pub fn play_playlist(
+        &mut self,
+        videos: &[Video],
+        start: usize,
+        repeat_mode: RepeatMode,
+    ) -> color_eyre::Result<()> {
+        if self.is_nowplaying {
+            self.kill_mpv();
         }
+        let Some(video) = videos.get(start) else {
+            return Ok(());
+        };
+        self.is_nowplaying = true;
+        self.now_playing = video.clone();
+        self.now_playing_url = video.url();
+        self.started.push(video.clone());
 
-        // amount of times it tries to connect to mpv socket.
+        let mut command = self.mpv_command();
+        command
+            .arg("--idle=yes")
+            .arg("--prefetch-playlist=yes")
+            .arg("--gapless-audio=yes")
+            .arg(self.mpv_ipc_arg()?);
+        self.mpv_process = Some(spawn_logged(command)?);
+        self.is_playlist = true;
+        // the entries are loaded over IPC as soon as the socket is up, see try_connect_mpv
+        self.playlist_pending = Some((videos.to_vec(), start));
+        self.playlist_repeat_mode = repeat_mode;
         self.mpv_connect_attempts = 10;
+        Ok(())
+    }
+
+    /// Adds an entry to the end of mpv's playlist.
+    pub fn playlist_append(&mut self, video: &Video) -> color_eyre::Result<()> {
+        if let Some((ref mut pending, _)) = self.playlist_pending {
+            pending.push(video.clone());
+            return Ok(());
+        }
+        self.send_mpv_command(
+            json!(["loadfile", video.url(), "append"]),
+            MpvRequest::Command,
+        )
+    }
+
+    /// Makes mpv play the entry at `index` of its playlist.
+    pub fn playlist_jump(&mut self, index: usize) -> color_eyre::Result<()> {
+        if let Some((_, ref mut start)) = self.playlist_pending {
+            *start = index;
+            return Ok(());
+        }
+        // mpv ignores setting playlist-pos to what it already is, and reports no change either
+        if self.playlist_pos == Some(index) {
+            return self
+                .send_mpv_command(json!(["playlist-play-index", index]), MpvRequest::Command);
+        }
+        self.playlist_expected_pos = Some(index);
+        self.send_mpv_command(
+            json!(["set_property", "playlist-pos", index]),
+            MpvRequest::Command,
+        )
+    }
 
+    /// Removes every entry but the one playing from mpv's playlist.
+    pub fn playlist_clear(&mut self) -> color_eyre::Result<()> {
+        if let Some((ref mut pending, ref mut start)) = self.playlist_pending {
+            *pending = vec![self.now_playing.clone()];
+            *start = 0;
+            return Ok(());
+        }
+        self.send_mpv_command(json!(["playlist-clear"]), MpvRequest::Command)
+    }
+
+    /// Lets go of mpv's playlist once the queue is cleared. Only the playing track is kept, and mpv
+    /// quits when it's done, the same as a track played without gapless playback.
+    pub fn playlist_release(&mut self) -> color_eyre::Result<()> {
+        if self.playlist_pending.is_some() {
+            // nothing is loaded yet, so the track starts on its own
+            let url = self.now_playing_url.clone();
+            self.kill_mpv();
+            return self.spawn_mpv(&url);
+        }
+        self.playlist_set_repeat(RepeatMode::Off)?;
+        self.playlist_clear()?;
+        self.send_mpv_command(json!(["set_property", "idle", "no"]), MpvRequest::Command)?;
+        self.is_playlist = false;
+        self.playlist_expected_pos = None;
+        self.playlist_sync_pos = None;
         Ok(())
     }
 
+    /// Removes the entry at `index` from mpv's playlist. mpv skips ahead if it was playing.
+    pub fn playlist_remove(&mut self, index: usize) -> color_eyre::Result<()> {
+        if let Some((ref mut pending, _)) = self.playlist_pending {
+            if index < pending.len() {
+                pending.remove(index);
+            }
+            return Ok(());
+        }
+        self.send_mpv_command(json!(["playlist-remove", index]), MpvRequest::Command)
+    }
+
+    /// Moves the entry at `from` in mpv's playlist so that it ends up at index `to`.
+    pub fn playlist_move(&mut self, from: usize, to: usize) -> color_eyre::Result<()> {
+        if let Some((ref mut pending, _)) = self.playlist_pending {
+            if from < pending.len() && to < pending.len() {
+                let video = pending.remove(from);
+                pending.insert(to, video);
+            }
+            return Ok(());
+        }
+        // mpv moves the entry in front of the one at the second index, counted before the move
+        let before = if to > from { to + 1 } else { to };
+        self.send_mpv_command(json!(["playlist-move", from, before]), MpvRequest::Command)
+    }
+
+    /// Rebuilds mpv's playlist to match `videos` without interrupting the entry that is playing,
+    /// which ends up at `current_index`. If the queue no longer has it, mpv moves on to the first entry.
+    pub fn playlist_sync(
+        &mut self,
+        videos: &[Video],
+        current_index: Option<usize>,
+    ) -> color_eyre::Result<()> {
+        if let Some((ref mut pending, ref mut start)) = self.playlist_pending {
+            *pending = videos.to_vec();
+            *start = current_index.unwrap_or(0);
+            return Ok(());
+        }
+        // playlist-clear keeps only the playing entry, at index 0, until it's moved back in place
+        self.playlist_sync_pos = current_index.map(|current_index| (current_index, Instant::now()));
+        self.playlist_clear()?;
+        for (index, video) in videos.iter().enumerate() {
+            if Some(index) != current_index {
+                self.send_mpv_command(
+                    json!(["loadfile", video.url(), "append"]),
+                    MpvRequest::Command,
+                )?;
+            }
+        }
+        match current_index {
+            Some(current_index) => self.playlist_move(0, current_index),
+            None => self.playlist_remove(0),
+        }
+    }
+
+    /// Mirrors the queue's repeat mode onto mpv's own looping, so repeats stay gapless too.
+    pub fn playlist_set_repeat(&mut self, repeat_mode: RepeatMode) -> color_eyre::Result<()> {
+        self.playlist_repeat_mode = repeat_mode;
+        if self.playlist_pending.is_some() {
+            // applied by load_pending_playlist once mpv is reachable
+            return Ok(());
+        }
+        let (loop_file, loop_playlist) = match repeat_mode {
+            RepeatMode::Off => ("no", "no"),
+            RepeatMode::One => ("inf", "no"),
+            RepeatMode::All => ("no", "inf"),
+        };
+        self.send_mpv_command(
+            json!(["set_property", "loop-file", loop_file]),
+            MpvRequest::Command,
+        )?;
+        self.send_mpv_command(
+            json!(["set_property", "loop-playlist", loop_playlist]),
+            MpvRequest::Command,
+        )
+    }
+
+    /// Whether a `playlist-pos` change comes from [`Player::playlist_jump`] rather than mpv moving on
+    /// by itself. Only the next report is checked against the jump, so one that never shows up
+    /// doesn't hide later moves. Everything up to the end of a [`Player::playlist_sync`] is expected too.
+    pub fn playlist_pos_is_expected(&mut self, pos: Option<usize>) -> bool {
+        if let Some((target, since)) = self.playlist_sync_pos {
+            if since.elapsed() < PLAYLIST_SYNC_TIMEOUT {
+                if pos == Some(target) {
+                    self.playlist_sync_pos = None;
+                }
+                return true;
+            }
+            self.playlist_sync_pos = None;
+        }
+        self.playlist_expected_pos
+            .take()
+            .is_some_and(|expected| pos == Some(expected))
+    }
+
     pub fn play_video_url(&mut self, url: String) -> color_eyre::Result<()> {
         if self.is_nowplaying {
             self.kill_mpv();
         }
         self.is_nowplaying = true;
-        match self.playback_mode {
-            PlaybackMode::Audio => {
-                let child = Command::new("mpv")
-                    .arg("--ytdl-format=bestaudio")
-                    .arg(url)
-                    .arg("--input-ipc-server=/tmp/mpv-socket")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .stdin(Stdio::null())
-                    .spawn()?;
-                self.mpv_process = Some(child);
-            }
-            PlaybackMode::Video => {
-                let child = Command::new("mpv")
-                    .arg(url)
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .spawn()?;
-                self.mpv_process = Some(child);
-            }
+        self.now_playing = Video {
+            id: video_id_from_url(&url).unwrap_or_default(),
+            title: url.clone(),
+            ..Default::default()
+        };
+        // only YouTube videos can be replayed from the history
+        if !self.now_playing.id.is_empty() {
+            self.started.push(self.now_playing.clone());
         }
+        self.spawn_mpv(&url)?;
+        self.now_playing_url = url;
+        Ok(())
+    }
+
+    /// Starts mpv on a single `url`, controlled over IPC in audio and video mode alike.
+    fn spawn_mpv(&mut self, url: &str) -> color_eyre::Result<()> {
+        let mut command = self.mpv_command();
+        command.arg(url).arg(self.mpv_ipc_arg()?);
+        self.mpv_process = Some(spawn_logged(command)?);
+        // amount of times it tries to connect to mpv socket.
         self.mpv_connect_attempts = 10;
         Ok(())
     }
 
-    pub fn try_connect_mpv(&mut self) {
+    /// An mpv command with the options every mpv ymp starts needs, plus the configured ones for
+    /// the current playback mode and where to resume from, if anywhere.
+    fn mpv_command(&mut self) -> Command {
+        let mut command = Command::new(&self.mpv_path);
+        if let Some(resume) = self.resume.take() {
+            command.arg(format!("--start={}", resume.position));
+            if resume.paused {
+                command.arg("--pause");
+            }
+            if let Some(volume) = resume.volume {
+                command.arg(format!("--volume={volume}"));
+            }
+            self.reset_start = true;
+        }
+        let (mode, default_format) = match self.playback_mode {
+            PlaybackMode::Audio => (&self.mpv_audio, Some("bestaudio")),
+            PlaybackMode::Video => (&self.mpv_video, None),
+        };
+        if let Some(profile) = &mode.profile {
+            command.arg(format!("--profile={profile}"));
+        }
+        if let Some(format) = mode.ytdl_format.as_deref().or(default_format) {
+            command.arg(format!("--ytdl-format={format}"));
+        }
+        if let Some(cookies) = &self.cookies {
+            // passed on to the yt-dlp mpv runs to resolve the URL
+            command.arg(format!(
+                "--ytdl-raw-options-append={}",
+                cookies.ytdl_option()
+            ));
+        }
+        command.args(&self.mpv_args).args(&mode.args);
+        command
+    }
+
+    /// `$XDG_RUNTIME_DIR/ymp/mpv-<pid>.sock`. Without a runtime dir, a directory of its own in the
+    /// temp dir, since anyone can create files there.
+    fn init_socket_path() -> PathBuf {
+        let pid = process::id();
+        let dir = match dirs::runtime_dir() {
+            Some(dir) => dir.join("ymp"),
+            None => env::temp_dir().join(format!("ymp-{pid}")),
+        };
+        dir.join(format!("mpv-{pid}.sock"))
+    }
+
+    /// Creates the socket's directory, readable by this user only, and returns the mpv option
+    /// that makes mpv listen on the socket.
+    fn mpv_ipc_arg(&self) -> color_eyre::Result<String> {
+        if let Some(dir) = self.mpv_socket.parent() {
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .map_err(|e| {
+                    color_eyre::eyre::eyre!("Could not create socket dir {}: {e}", dir.display())
+                })?;
+            // the dir may be left over with other permissions
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(format!("--input-ipc-server={}", self.mpv_socket.display()))
+    }
+
+    pub fn try_connect_mpv(&mut self) -> color_eyre::Result<()> {
         if self.mpv_connect_attempts == 0 {
-            return;
+            return Ok(());
         }
-        match UnixStream::connect("/tmp/mpv-socket") {
+        match UnixStream::connect(&self.mpv_socket) {
             Ok(o) => {
+                // mpv is polled from the render loop, so reads must never block.
+                if o.set_nonblocking(true).is_err() {
+                    self.mpv_connect_attempts -= 1;
+                    return Ok(());
+                }
                 self.mpv_stream = Some(o);
                 self.mpv_connect_attempts = 0;
+                self.observe_properties()?;
+                self.load_pending_playlist()?;
             }
             Err(_) => {
                 self.mpv_connect_attempts -= 1;
             }
         }
+        Ok(())
+    }
+
+    fn load_pending_playlist(&mut self) -> color_eyre::Result<()> {
+        let Some((videos, start)) = self.playlist_pending.take() else {
+            return Ok(());
+        };
+        for video in &videos {
+            self.send_mpv_command(
+                json!(["loadfile", video.url(), "append"]),
+                MpvRequest::Command,
+            )?;
+        }
+        self.playlist_set_repeat(self.playlist_repeat_mode)?;
+        self.playlist_jump(start)
+    }
+
+    /// Subscribes to [`OBSERVED_PROPERTIES`]. The observer id is the property's index in that list.
+    fn observe_properties(&mut self) -> color_eyre::Result<()> {
+        for (id, property) in OBSERVED_PROPERTIES.iter().enumerate() {
+            self.send_mpv_command(
+                json!(["observe_property", id + 1, property]),
+                MpvRequest::Command,
+            )?;
+        }
+        Ok(())
     }
     pub fn kill_mpv(&mut self) {
         self.mpv_stream.take();
+        self.mpv_read_buffer.clear();
+        self.mpv_write_buffer.clear();
+        self.mpv_pending_requests.clear();
+        self.mpv_end_reason = None;
+        self.is_playlist = false;
+        self.playlist_pending = None;
+        self.playlist_expected_pos = None;
+        self.playlist_pos = None;
+        self.playlist_sync_pos = None;
+        self.time_pos = None;
+        self.duration = None;
+        self.is_paused = false;
+        self.reset_start = false;
 
         if let Some(mut child) = self.mpv_process.take() {
+            log::debug!("Stopping mpv (pid {})", child.id());
             if let Err(e) = child.kill() {
-                eprintln!("Could not kill mpv child process, call idf: {e}");
+                self.mpv_pending_events
+                    .push(MpvEvent::Error(format!("Could not stop mpv: {e}")));
             }
             if let Err(e) = child.wait() {
-                eprintln!("Could not wait on mpv child process: {e}");
+                self.mpv_pending_events.push(MpvEvent::Error(format!(
+                    "Could not wait for mpv to stop: {e}"
+                )));
             }
         }
         self.mpv_process = None;
-        if let Err(e) = fs::remove_file("/tmp/mpv-socket")
+        if let Err(e) = fs::remove_file(&self.mpv_socket)
             && e.kind() != ErrorKind::NotFound
         {
-            eprintln!("Could not remove /tmp/mpv-socket file: {e}");
+            self.mpv_pending_events.push(MpvEvent::Error(format!(
+                "Could not remove {}: {e}",
+                self.mpv_socket.display()
+            )));
+        }
+    }
+    /// Reads everything mpv has written to the socket so far and returns it as typed events.
+    ///
+    /// Never blocks; partial lines are kept until the rest arrives on a later call.
+    pub fn poll_mpv(&mut self) -> Vec<MpvEvent> {
+        let mut events = std::mem::take(&mut self.mpv_pending_events);
+        let mut disconnected = false;
+        if let Some(ref mut stream) = self.mpv_stream {
+            let mut buf = [0u8; 4096];
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => {
+                        disconnected = true;
+                        break;
+                    }
+                    Ok(n) => self.mpv_read_buffer.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        events.push(MpvEvent::Error(format!(
+                            "Could not read from mpv socket: {e}"
+                        )));
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if disconnected {
+            self.mpv_stream = None;
+        }
+        // mpv takes the rest of a long playlist once it has caught up
+        if let Err(e) = self.flush_mpv_writes() {
+            events.push(MpvEvent::Error(e.to_string()));
+        }
+
+        while let Some(newline) = self.mpv_read_buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.mpv_read_buffer.drain(..=newline).collect();
+            let text = String::from_utf8_lossy(&line);
+            // property changes come several times a second, so they only show at trace level
+            if text.contains("\"property-change\"") {
+                log::trace!("mpv -> {}", text.trim_end());
+            } else {
+                log::debug!("mpv -> {}", text.trim_end());
+            }
+            if let Some(event) = self.parse_mpv_message(&line) {
+                events.push(event);
+            }
+        }
+
+        // --start would apply to every later entry of a gapless playlist too
+        if self.reset_start && self.time_pos.is_some() {
+            self.reset_start = false;
+            if let Err(e) = self.send_mpv_command(
+                json!(["set_property", "start", "none"]),
+                MpvRequest::Command,
+            ) {
+                events.push(MpvEvent::Error(e.to_string()));
+            }
+        }
+
+        // Checked after reading the socket so the final end-file event is never lost.
+        let exited = match self.mpv_process {
+            Some(ref mut child) => matches!(child.try_wait(), Ok(Some(_))),
+            None => false,
+        };
+        if exited {
+            let reason = self.mpv_end_reason.take();
+            self.kill_mpv();
+            self.is_nowplaying = false;
+            events.push(MpvEvent::Exited(reason));
         }
+        events
     }
-    fn send_mpv_command(&mut self, args: Vec<&str>) -> color_eyre::Result<()> {
+
+    fn parse_mpv_message(&mut self, line: &[u8]) -> Option<MpvEvent> {
+        let message: Value = serde_json::from_slice(line).ok()?;
+
+        if let Some(event) = message.get("event").and_then(Value::as_str) {
+            return match event {
+                "end-file" => {
+                    let reason = message
+                        .get("reason")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown")
+                        .to_string();
+                    self.mpv_end_reason = Some(reason.clone());
+                    Some(MpvEvent::EndFile(reason))
+                }
+                "property-change" => {
+                    let data = message.get("data");
+                    match message.get("name").and_then(Value::as_str)? {
+                        "time-pos" => {
+                            self.time_pos = data.and_then(Value::as_f64);
+                            Some(MpvEvent::TimePos(self.time_pos))
+                        }
+                        "duration" => {
+                            self.duration = data.and_then(Value::as_f64);
+                            Some(MpvEvent::Duration(self.duration))
+                        }
+                        "pause" => {
+                            self.is_paused = data.and_then(Value::as_bool).unwrap_or(false);
+                            Some(MpvEvent::Pause(self.is_paused))
+                        }
+                        // mpv reports -1 when it has no current entry
+                        "playlist-pos" => {
+                            let pos = data.and_then(Value::as_u64).map(|pos| pos as usize);
+                            // e.g. the value reported right after observing it, before anything played
+                            if pos == self.playlist_pos {
+                                return None;
+                            }
+                            self.playlist_pos = pos;
+                            Some(MpvEvent::PlaylistPos(pos))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+        }
+
+        let request_id = message.get("request_id").and_then(Value::as_u64)?;
+        let request = self.mpv_pending_requests.remove(&request_id)?;
+        let error = message
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("success");
+        if error != "success" {
+            return Some(MpvEvent::Error(format!(
+                "mpv {request:?} request failed: {error}"
+            )));
+        }
+        let data = message.get("data");
+        match request {
+            MpvRequest::Command => None,
+            MpvRequest::Volume => {
+                let volume = data.and_then(Value::as_f64)?;
+                self.volume = Some(volume);
+                Some(MpvEvent::Volume(volume))
+            }
+        }
+    }
+
+    /// Writes a JSON IPC command to mpv, tagged with a fresh `request_id` so the reply can be matched
+    /// up in [`Player::poll_mpv`].
+    fn send_mpv_command(&mut self, command: Value, request: MpvRequest) -> color_eyre::Result<()> {
         if self.mpv_stream.is_none() {
-            eprintln!(
-                "mpv_stream doesn't exist. Controls won't work right now. This is open source go to {} at line {}",
-                file!(),
-                line!()
-            );
+            return Err(if self.mpv_process.is_none() {
+                color_eyre::eyre::eyre!("Nothing is playing")
+            } else {
+                color_eyre::eyre::eyre!("mpv isn't listening for controls yet")
+            });
+        }
+        self.mpv_request_id += 1;
+        let request_id = self.mpv_request_id;
+        let message = format!(
+            "{}\n",
+            json!({ "command": command, "request_id": request_id })
+        );
+        log::debug!("mpv <- {}", message.trim_end());
+        self.mpv_write_buffer.extend_from_slice(message.as_bytes());
+        self.mpv_pending_requests.insert(request_id, request);
+        self.flush_mpv_writes()
+    }
+
+    /// Writes as much of the buffered commands as the socket takes without blocking. The rest
+    /// stays buffered, so a command is never cut off halfway.
+    fn flush_mpv_writes(&mut self) -> color_eyre::Result<()> {
+        let Some(ref mut stream) = self.mpv_stream else {
             return Ok(());
+        };
+        while !self.mpv_write_buffer.is_empty() {
+            match stream.write(&self.mpv_write_buffer) {
+                Ok(0) => break,
+                Ok(written) => {
+                    self.mpv_write_buffer.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.mpv_write_buffer.clear();
+                    return Err(color_eyre::eyre::eyre!(
+                        "Could not write to the mpv socket: {e}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Player {
+    /// Stops mpv and removes the socket when ymp quits, returns an error or unwinds from a panic.
+    fn drop(&mut self) {
+        if self.mpv_socket.as_os_str().is_empty() {
+            return;
         }
-        let mut vec_args: Vec<String> = Vec::new();
-        for arg in args {
-            vec_args.push(format!("\"{}\"", arg));
+        self.kill_mpv();
+        // only succeeds once no other instance has its socket in there
+        if let Some(dir) = self.mpv_socket.parent() {
+            let _ = fs::remove_dir(dir);
         }
-        let json_args = vec_args.join(",");
-        let message = format!("{{\"command\": [{json_args}]}}\n");
-        if let Some(ref mut stream) = self.mpv_stream
-            && let Err(e) = stream.write_all(message.as_bytes())
-        {
-            return Err(color_eyre::eyre::eyre!(
-                "Could not write to UnixStream at send_mpv_command(): {e} "
-            ));
+    }
+}
+
+/// Spawns `command` with its stderr going to the log file, one line at a time, from a thread of its
+/// own that ends with the process.
+fn spawn_logged(mut command: Command) -> color_eyre::Result<Child> {
+    let capture = log::log_enabled!(target: "ymp::mpv", log::Level::Warn);
+    command
+        .stdout(Stdio::null())
+        .stdin(Stdio::null())
+        .stderr(if capture {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        });
+    log::info!("Starting {command:?}");
+    let mut child = command.spawn().map_err(|e| {
+        color_eyre::eyre::eyre!("Could not start {}: {e}", command.get_program().display())
+    })?;
+    if let Some(stderr) = child.stderr.take() {
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                log::warn!(target: "ymp::mpv", "{line}");
+            }
+        });
+    }
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_commands_whole_when_the_socket_is_full() {
+        let (ours, mut mpv) = UnixStream::pair().unwrap();
+        ours.set_nonblocking(true).unwrap();
+        mpv.set_nonblocking(true).unwrap();
+        let mut player = Player::default();
+        player.mpv_stream = Some(ours);
+        let count = 5000;
+        for index in 0..count {
+            let url = format!("https://www.youtube.com/watch?v={index:011}");
+            player
+                .send_mpv_command(json!(["loadfile", url, "append"]), MpvRequest::Command)
+                .unwrap();
+        }
+        assert!(!player.mpv_write_buffer.is_empty());
+        let mut received = Vec::new();
+        let mut buf = [0u8; 65536];
+        while received.iter().filter(|b| **b == b'\n').count() < count {
+            match mpv.read(&mut buf) {
+                Ok(read) => received.extend_from_slice(&buf[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    assert!(!player.mpv_write_buffer.is_empty());
+                    player.poll_mpv();
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+        for (index, line) in received.split(|b| *b == b'\n').take(count).enumerate() {
+            let message: Value = serde_json::from_slice(line).unwrap();
+            assert_eq!(message["request_id"], index as u64 + 1);
+        }
+    }
+
+    #[test]
+    fn parses_seek_targets() {
+        assert_eq!(SeekTarget::parse("50%"), Some(SeekTarget::Percent(50.0)));
+        assert_eq!(
+            SeekTarget::parse(" 1:23 "),
+            Some(SeekTarget::Absolute(83.0))
+        );
+        assert_eq!(
+            SeekTarget::parse("1:02:03"),
+            Some(SeekTarget::Absolute(3723.0))
+        );
+        for input in [
+            "nan", "inf", "inf%", "NaN%", "1:inf", "101%", "-5", "1:2:3:4", "",
+        ] {
+            assert_eq!(SeekTarget::parse(input), None, "{input}");
         }
-        Ok(())
     }
 }
@@ -0,0 +1,348 @@
+use crate::types::{Video, video_id_from_url};
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaylistFormat {
+    /// Extended M3U, one `#EXTINF` line per entry.
+    M3u,
+    Xspf,
+    /// One YouTube URL per line.
+    Urls,
+}
+
+impl PlaylistFormat {
+    /// Picks the format from the file extension. Anything that isn't `.m3u`, `.m3u8` or `.xspf`
+    /// is treated as a plain list of URLs.
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("m3u" | "m3u8") => PlaylistFormat::M3u,
+            Some("xspf") => PlaylistFormat::Xspf,
+            _ => PlaylistFormat::Urls,
+        }
+    }
+}
+
+/// Writes `videos` to `path` in the format its extension asks for. `title` names the playlist
+/// where the format has room for it.
+pub fn export(videos: &[Video], title: &str, path: &Path) -> color_eyre::Result<()> {
+    let contents = match PlaylistFormat::from_path(path) {
+        PlaylistFormat::M3u => to_m3u(videos),
+        PlaylistFormat::Xspf => to_xspf(videos, title),
+        PlaylistFormat::Urls => to_urls(videos),
+    };
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::DirBuilder::new().recursive(true).create(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Reads the videos listed in `path`. Entries that aren't YouTube videos are skipped.
+pub fn import(path: &Path) -> color_eyre::Result<Vec<Video>> {
+    let contents = fs::read_to_string(path)?;
+    let videos = match PlaylistFormat::from_path(path) {
+        PlaylistFormat::M3u => from_m3u(&contents),
+        PlaylistFormat::Xspf => from_xspf(&contents),
+        PlaylistFormat::Urls => from_urls(&contents),
+    };
+    if videos.is_empty() {
+        return Err(color_eyre::eyre::eyre!(
+            "No YouTube videos found in {}",
+            path.display()
+        ));
+    }
+    Ok(videos)
+}
+
+fn to_m3u(videos: &[Video]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for video in videos {
+        m3u.push_str(&format!("#EXTINF:-1,{}\n", single_line(&video.title)));
+        if !video.uploader.is_empty() {
+            m3u.push_str(&format!("#EXTART:{}\n", single_line(&video.uploader)));
+        }
+        m3u.push_str(&video.url());
+        m3u.push('\n');
+    }
+    m3u
+}
+
+fn from_m3u(contents: &str) -> Vec<Video> {
+    let mut videos = Vec::new();
+    let (mut title, mut uploader) = (None, None);
+    for line in contents.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<duration>,<title>
+            title = info.split_once(',').map(|(_, title)| title.to_string());
+        } else if let Some(artist) = line.strip_prefix("#EXTART:") {
+            uploader = Some(artist.to_string());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            if let Some(video) = video_from(line, title.take(), uploader.take()) {
+                videos.push(video);
+            }
+            (title, uploader) = (None, None);
+        }
+    }
+    videos
+}
+
+fn to_xspf(videos: &[Video], title: &str) -> String {
+    let mut xspf = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    xspf.push_str(&format!("  <title>{}</title>\n", xml_escape(title)));
+    xspf.push_str("  <trackList>\n");
+    for video in videos {
+        xspf.push_str("    <track>\n");
+        xspf.push_str(&format!(
+            "      <location>{}</location>\n",
+            xml_escape(&video.url())
+        ));
+        xspf.push_str(&format!(
+            "      <title>{}</title>\n",
+            xml_escape(&video.title)
+        ));
+        if !video.uploader.is_empty() {
+            xspf.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                xml_escape(&video.uploader)
+            ));
+        }
+        xspf.push_str("    </track>\n");
+    }
+    xspf.push_str("  </trackList>\n</playlist>\n");
+    xspf
+}
+
+fn from_xspf(contents: &str) -> Vec<Video> {
+    xml_elements(contents, "track")
+        .filter_map(|track| {
+            let location = xml_element(track, "location")?;
+            video_from(
+                &location,
+                xml_element(track, "title"),
+                xml_element(track, "creator"),
+            )
+        })
+        .collect()
+}
+
+fn to_urls(videos: &[Video]) -> String {
+    videos.iter().map(|video| video.url() + "\n").collect()
+}
+
+fn from_urls(contents: &str) -> Vec<Video> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| video_from(line, None, None))
+        .collect()
+}
+
+/// Builds a [`Video`] from a URL and whatever the file said about it. Without a title the URL
+/// stands in for one.
+fn video_from(url: &str, title: Option<String>, uploader: Option<String>) -> Option<Video> {
+    let id = video_id_from_url(url)?;
+    Some(Video {
+        title: title
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| url.to_string()),
+        uploader: uploader.unwrap_or_default(),
+        id,
+        ..Default::default()
+    })
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Decodes the five named entities and numeric `&#NN;`/`&#xNN;` references. Anything else is
+/// left as it is.
+fn xml_unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let ch = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "amp" => '&',
+                entity => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix('x') {
+                        Some(hex) => u32::from_str_radix(hex, 16),
+                        None => number.parse(),
+                    };
+                    char::from_u32(code.ok()?)?
+                }
+            };
+            Some((ch, end + 1))
+        });
+        match decoded {
+            Some((ch, length)) => {
+                unescaped.push(ch);
+                rest = &rest[length..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// The name of a tag without its namespace prefix.
+fn local_name(tag: &str) -> &str {
+    let name = tag
+        .split(|ch: char| ch.is_whitespace() || ch == '/')
+        .next()
+        .unwrap_or_default();
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Contents of every `<name …>…</name>` element in `xml`, whatever their attributes or namespace
+/// prefix. Elements of the same name nested in each other aren't told apart.
+fn xml_elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        loop {
+            let start = rest.find('<')?;
+            let end = start + rest[start..].find('>')?;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if tag.starts_with(['/', '!', '?']) || local_name(tag) != name {
+                continue;
+            }
+            if tag.ends_with('/') {
+                return Some("");
+            }
+            let mut offset = 0;
+            while let Some(close) = rest[offset..].find("</") {
+                let close = offset + close;
+                let close_end = close + rest[close..].find('>')?;
+                if local_name(rest[close + 2..close_end].trim()) == name {
+                    let contents = &rest[..close];
+                    rest = &rest[close_end + 1..];
+                    return Some(contents);
+                }
+                offset = close_end + 1;
+            }
+            return None;
+        }
+    })
+}
+
+/// Text of the first `<name>…</name>` element in `xml`.
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    xml_elements(xml, name)
+        .next()
+        .map(|text| xml_unescape(text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn videos() -> Vec<Video> {
+        vec![
+            Video {
+                id: "dQw4w9WgXcQ".to_string(),
+                title: "Never <Gonna> \"Give\" You Up & 'more' — é".to_string(),
+                uploader: "Rick Astley".to_string(),
+                ..Default::default()
+            },
+            Video {
+                id: "jNQXAC9IVRw".to_string(),
+                title: "Me at the zoo".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn summary(videos: &[Video]) -> Vec<(&str, &str, &str)> {
+        videos
+            .iter()
+            .map(|video| {
+                (
+                    video.id.as_str(),
+                    video.title.as_str(),
+                    video.uploader.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_m3u() {
+        let videos = videos();
+        assert_eq!(summary(&from_m3u(&to_m3u(&videos))), summary(&videos));
+    }
+
+    #[test]
+    fn round_trips_xspf() {
+        let videos = videos();
+        assert_eq!(
+            summary(&from_xspf(&to_xspf(&videos, "a & b"))),
+            summary(&videos)
+        );
+    }
+
+    #[test]
+    fn reads_xspf_with_attributes_and_prefixes() {
+        let xspf = r#"<?xml version="1.0"?>
+<x:playlist xmlns:x="http://xspf.org/ns/0/"><x:title>List</x:title><x:trackList>
+  <x:track xml:id="one">
+    <x:location>https://youtu.be/dQw4w9WgXcQ</x:location>
+    <x:title>Caf&#233; &#x2014; &lt;live&gt;</x:title>
+    <x:creator/>
+  </x:track>
+  <track><location>https://example.com/not-youtube</location></track>
+  <track ><location>https://www.youtube.com/watch?v=jNQXAC9IVRw&amp;t=1</location></track>
+</x:trackList></x:playlist>"#;
+        assert_eq!(
+            summary(&from_xspf(xspf)),
+            vec![
+                ("dQw4w9WgXcQ", "Café — <live>", ""),
+                (
+                    "jNQXAC9IVRw",
+                    "https://www.youtube.com/watch?v=jNQXAC9IVRw&t=1",
+                    ""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_entities_alone() {
+        assert_eq!(
+            xml_unescape("a &nbsp; &#xZZ; & b&amp;c"),
+            "a &nbsp; &#xZZ; & b&c"
+        );
+        assert_eq!(xml_unescape("&amp;lt;"), "&lt;");
+    }
+}
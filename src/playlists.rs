@@ -0,0 +1,139 @@
+use crate::types::Video;
+use ratatui::widgets::ListState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    #[serde(default)]
+    pub videos: Vec<Video>,
+}
+
+/// Named, saved playlists. Kept next to the queuelist in `playlists.json`.
+#[derive(Default, Debug)]
+pub struct Playlists {
+    playlists_path: String,
+    playlists: Vec<Playlist>,
+    playlists_state: ListState,
+}
+
+impl Playlists {
+    pub fn default() -> Self {
+        let playlists_path = Self::init_playlists_path();
+        let playlists = Vec::new();
+        let playlists_state = ListState::default().with_selected(Some(0));
+        Self {
+            playlists_path,
+            playlists,
+            playlists_state,
+        }
+    }
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&mut self, name: &str) -> color_eyre::Result<()> {
+        let name = self.check_name(name)?;
+        self.playlists.push(Playlist {
+            name,
+            videos: Vec::new(),
+        });
+        self.playlists_state.select(Some(self.playlists.len() - 1));
+        Ok(())
+    }
+    pub fn rename(&mut self, index: usize, name: &str) -> color_eyre::Result<()> {
+        if self
+            .playlists
+            .get(index)
+            .is_some_and(|playlist| playlist.name == name.trim())
+        {
+            return Ok(());
+        }
+        let name = self.check_name(name)?;
+        self.get_mut(index)?.name = name;
+        Ok(())
+    }
+    pub fn delete(&mut self, index: usize) -> color_eyre::Result<Playlist> {
+        self.get_mut(index)?;
+        let playlist = self.playlists.remove(index);
+        let last = self.playlists.len().saturating_sub(1);
+        if self
+            .playlists_state
+            .selected()
+            .is_some_and(|selected| selected > last)
+        {
+            self.playlists_state.select(Some(last));
+        }
+        Ok(playlist)
+    }
+    pub fn add_video(&mut self, index: usize, video: Video) -> color_eyre::Result<()> {
+        self.get_mut(index)?.videos.push(video);
+        Ok(())
+    }
+
+    pub fn retrieve_playlists(&mut self) -> color_eyre::Result<()> {
+        if fs::exists(&self.playlists_path)? {
+            let playlists = fs::read_to_string(&self.playlists_path)?;
+            self.playlists = serde_json::from_str(playlists.as_str())?;
+        }
+        Ok(())
+    }
+    pub fn save_playlists(&self) -> color_eyre::Result<()> {
+        if let Some((path, _filename)) = self.playlists_path.rsplit_once("/") {
+            fs::DirBuilder::new().recursive(true).create(path)?;
+        }
+        let playlists_json = serde_json::to_string_pretty(&self.playlists)?;
+        fs::write(self.playlists_path.clone(), playlists_json)?;
+        Ok(())
+    }
+
+    pub fn playlists(&self) -> &[Playlist] {
+        &self.playlists
+    }
+    pub fn playlists_state(&mut self) -> &mut ListState {
+        &mut self.playlists_state
+    }
+    /// Index of the playlist called `name`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|playlist| playlist.name == name.trim())
+    }
+    /// The playlist under the cursor.
+    pub fn selected(&self) -> Option<&Playlist> {
+        self.playlists.get(self.playlists_state.selected()?)
+    }
+
+    fn get_mut(&mut self, index: usize) -> color_eyre::Result<&mut Playlist> {
+        self.playlists.get_mut(index).ok_or_else(|| {
+            color_eyre::eyre::eyre!("Index out of bounds in {} at {} ", file!(), line!())
+        })
+    }
+    /// Playlist names must be non-empty and unique.
+    fn check_name(&self, name: &str) -> color_eyre::Result<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(color_eyre::eyre::eyre!("Playlist name can't be empty"));
+        }
+        if self.playlists.iter().any(|playlist| playlist.name == name) {
+            return Err(color_eyre::eyre::eyre!(
+                "A playlist named \"{name}\" already exists"
+            ));
+        }
+        Ok(name.to_string())
+    }
+    fn init_playlists_path() -> String {
+        match dirs::data_local_dir() {
+            Some(mut path) => {
+                path.push("ymp");
+                path.push("playlists.json");
+                path.to_string_lossy().into_owned()
+            }
+            None => {
+                // TODO Add error handling for playlists path not accessible.
+                String::from("Placeholder")
+            }
+        }
+    }
+}
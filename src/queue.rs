@@ -1,24 +1,59 @@
-use crate::types::Video;
+use crate::types::{RepeatMode, Video};
 use ratatui::widgets::ListState;
-use std::fs;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs};
+
+/// Playback order settings, saved next to the queuelist in `queuemodes.json`.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct QueueModes {
+    #[serde(default)]
+    repeat_mode: RepeatMode,
+    #[serde(default)]
+    shuffle: bool,
+}
+
+/// Queue contents from before the last destructive edit, for [`Queue::undo`]. Adding or moving
+/// entries afterwards drops it.
+#[derive(Debug, Clone)]
+struct QueueSnapshot {
+    queuelist: Vec<Video>,
+    current_index: Option<usize>,
+}
 
 #[derive(Default, Debug)]
 pub struct Queue {
     queuelist_path: String,
+    queuemodes_path: String,
     queuelist: Vec<Video>,
     queuelist_state: ListState,
+    /// Entry that is playing right now. Separate from the cursor in `queuelist_state`.
+    current_index: Option<usize>,
+    modes: QueueModes,
+    /// Play order while shuffling, as indices into `queuelist`. The displayed order is left alone.
+    shuffle_order: Vec<usize>,
+    undo_snapshot: Option<QueueSnapshot>,
 }
 
 impl Queue {
     pub fn default() -> Self {
         // queuelist is saved here
-        let queuelist_path = Self::init_queuelist_path();
+        let queuelist_path = Self::init_queue_path("queuelist.json");
+        let queuemodes_path = Self::init_queue_path("queuemodes.json");
         let queuelist = Vec::new();
         let queuelist_state = ListState::default().with_selected(Some(0));
+        let current_index = None;
+        let modes = QueueModes::default();
+        let shuffle_order = Vec::new();
+        let undo_snapshot = None;
         Self {
             queuelist_path,
+            queuemodes_path,
             queuelist,
             queuelist_state,
+            current_index,
+            modes,
+            shuffle_order,
+            undo_snapshot,
         }
     }
     pub fn new() -> Self {
@@ -38,15 +73,275 @@ impl Queue {
                     line!()
                 ));
             };
-            self.queuelist.push(resultlist[index].clone());
+            self.push(resultlist[index].clone());
         }
         Ok(())
     }
+    pub fn push(&mut self, video: Video) {
+        // an undo from before this would quietly take the new entry out again
+        self.undo_snapshot = None;
+        self.queuelist.push(video);
+        if self.modes.shuffle {
+            // slot the new entry somewhere among the tracks that haven't played yet
+            let upcoming = self.shuffle_position().map_or(0, |position| position + 1);
+            let position = fastrand::usize(upcoming..=self.shuffle_order.len());
+            self.shuffle_order
+                .insert(position, self.queuelist.len() - 1);
+        }
+    }
+    /// Swaps the whole queue for `videos`. Can be undone with [`Queue::undo`].
+    pub fn replace(&mut self, videos: Vec<Video>) {
+        self.take_snapshot();
+        self.queuelist = videos;
+        self.current_index = None;
+        if self.modes.shuffle {
+            self.reshuffle(None);
+        }
+        self.queuelist_state.select(Some(0));
+    }
+    /// Makes the entry at `index` the current one and returns it for playback.
+    pub fn play_index(&mut self, index: usize) -> color_eyre::Result<Video> {
+        if self.queuelist.len() <= index {
+            return Err(color_eyre::eyre::eyre!(
+                "Index out of bounds in {} at {} ",
+                file!(),
+                line!()
+            ));
+        };
+        self.current_index = Some(index);
+        if self.modes.shuffle {
+            // picking a track by hand starts a new shuffled pass from it
+            self.reshuffle(Some(index));
+        }
+        Ok(self.queuelist[index].clone())
+    }
+    /// Moves on to the entry that should play after the current one, following the repeat and
+    /// shuffle modes. Returns `None` when playback should stop or nothing from the queue is playing.
+    pub fn play_next(&mut self) -> Option<Video> {
+        let current = self.current_index?;
+        let next = match self.modes.repeat_mode {
+            RepeatMode::One => Some(current),
+            RepeatMode::Off | RepeatMode::All if self.modes.shuffle => {
+                match self.shuffle_position() {
+                    Some(position) if position + 1 < self.shuffle_order.len() => {
+                        Some(self.shuffle_order[position + 1])
+                    }
+                    _ if self.modes.repeat_mode == RepeatMode::All => {
+                        // a fresh order for every pass through the queue
+                        self.reshuffle(None);
+                        self.shuffle_order.first().copied()
+                    }
+                    _ => None,
+                }
+            }
+            RepeatMode::Off => Some(current + 1).filter(|next| *next < self.queuelist.len()),
+            RepeatMode::All => Some((current + 1) % self.queuelist.len().max(1)),
+        };
+        match next.filter(|next| *next < self.queuelist.len()) {
+            Some(next) => {
+                self.current_index = Some(next);
+                Some(self.queuelist[next].clone())
+            }
+            None => {
+                self.current_index = None;
+                None
+            }
+        }
+    }
+    pub fn clear(&mut self) {
+        self.take_snapshot();
+        self.queuelist.clear();
+        self.shuffle_order.clear();
+        self.current_index = None;
+    }
+    /// Removes the entry at `index`. Can be undone with [`Queue::undo`].
+    pub fn remove(&mut self, index: usize) -> color_eyre::Result<Video> {
+        if self.queuelist.len() <= index {
+            return Err(color_eyre::eyre::eyre!(
+                "Index out of bounds in {} at {} ",
+                file!(),
+                line!()
+            ));
+        };
+        self.take_snapshot();
+        let video = self.queuelist.remove(index);
+        self.remap(|i| match i.cmp(&index) {
+            std::cmp::Ordering::Less => Some(i),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(i - 1),
+        });
+        Ok(video)
+    }
+    /// Moves the entry at `from` so that it ends up at index `to`, shifting the entries in between.
+    pub fn move_entry(&mut self, from: usize, to: usize) -> color_eyre::Result<()> {
+        if self.queuelist.len() <= from || self.queuelist.len() <= to {
+            return Err(color_eyre::eyre::eyre!(
+                "Index out of bounds in {} at {} ",
+                file!(),
+                line!()
+            ));
+        };
+        self.undo_snapshot = None;
+        let video = self.queuelist.remove(from);
+        self.queuelist.insert(to, video);
+        self.remap(|i| {
+            if i == from {
+                Some(to)
+            } else if from < to && (from + 1..=to).contains(&i) {
+                Some(i - 1)
+            } else if to < from && (to..from).contains(&i) {
+                Some(i + 1)
+            } else {
+                Some(i)
+            }
+        });
+        Ok(())
+    }
+    /// Moves the entry at `index` right behind the current one so it plays next, shuffled or not.
+    /// Returns where it ended up.
+    pub fn play_next_from(&mut self, index: usize) -> color_eyre::Result<usize> {
+        let target = match self.current_index {
+            Some(current) if current == index => return Ok(index),
+            Some(current) if current < index => current + 1,
+            // the current entry shifts down once this one is taken out in front of it
+            Some(current) => current,
+            None => 0,
+        };
+        self.move_entry(index, target)?;
+        if self.modes.shuffle {
+            self.shuffle_order.retain(|i| *i != target);
+            let next = self.shuffle_position().map_or(0, |position| position + 1);
+            self.shuffle_order.insert(next, target);
+        }
+        Ok(target)
+    }
+    /// Removes every entry whose [`Video::id`] already appeared earlier, keeping the current entry
+    /// if it is one of the copies. Returns how many were removed. Can be undone with [`Queue::undo`].
+    pub fn remove_duplicates(&mut self) -> usize {
+        let current_id = self
+            .current_index
+            .map(|index| self.queuelist[index].id.clone());
+        let mut seen = HashSet::new();
+        if let Some(ref current_id) = current_id {
+            seen.insert(current_id.clone());
+        }
+        let keep: Vec<bool> = self
+            .queuelist
+            .iter()
+            .enumerate()
+            .map(|(index, video)| {
+                Some(index) == self.current_index || seen.insert(video.id.clone())
+            })
+            .collect();
+        let removed = keep.iter().filter(|keep| !**keep).count();
+        if removed == 0 {
+            return 0;
+        }
+        self.take_snapshot();
+        let mut kept = 0;
+        let new_indices: Vec<Option<usize>> = keep
+            .iter()
+            .map(|keep| {
+                keep.then(|| {
+                    kept += 1;
+                    kept - 1
+                })
+            })
+            .collect();
+        let mut index = 0;
+        self.queuelist.retain(|_| {
+            index += 1;
+            keep[index - 1]
+        });
+        self.remap(|i| new_indices[i]);
+        removed
+    }
+    /// Restores the queue to how it was before the last remove, clear, deduplication or replace.
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_snapshot.take() else {
+            return false;
+        };
+        // whatever is playing now stays the current entry if the restored queue has it
+        let current_id = self
+            .current_index
+            .map(|index| self.queuelist[index].id.clone());
+        self.queuelist = snapshot.queuelist;
+        self.current_index = match current_id {
+            Some(id) => snapshot
+                .current_index
+                .filter(|index| {
+                    self.queuelist
+                        .get(*index)
+                        .is_some_and(|video| video.id == id)
+                })
+                .or_else(|| self.queuelist.iter().position(|video| video.id == id)),
+            None => None,
+        };
+        if self.modes.shuffle {
+            self.reshuffle(self.current_index);
+        }
+        true
+    }
+    fn take_snapshot(&mut self) {
+        self.undo_snapshot = Some(QueueSnapshot {
+            queuelist: self.queuelist.clone(),
+            current_index: self.current_index,
+        });
+    }
+    /// Renumbers the current entry and the shuffle order after entries moved around.
+    /// `map` takes an old index to its new one, or `None` if that entry is gone.
+    fn remap(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.current_index = self.current_index.and_then(&map);
+        self.shuffle_order = self.shuffle_order.iter().filter_map(|i| map(*i)).collect();
+    }
+    pub fn cycle_repeat_mode(&mut self) {
+        self.modes.repeat_mode.cycle();
+    }
+    pub fn toggle_shuffle(&mut self) {
+        self.modes.shuffle = !self.modes.shuffle;
+        if self.modes.shuffle {
+            self.reshuffle(self.current_index);
+        } else {
+            self.shuffle_order.clear();
+        }
+    }
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.modes.repeat_mode
+    }
+    pub fn is_shuffled(&self) -> bool {
+        self.modes.shuffle
+    }
+    /// Builds a new random play order. `first` (usually the current entry) is kept at the front so
+    /// the rest of the order is what plays next.
+    fn reshuffle(&mut self, first: Option<usize>) {
+        self.shuffle_order = (0..self.queuelist.len())
+            .filter(|index| Some(*index) != first)
+            .collect();
+        fastrand::shuffle(&mut self.shuffle_order);
+        if let Some(first) = first {
+            self.shuffle_order.insert(0, first);
+        }
+    }
+    /// Where the current entry sits in `shuffle_order`.
+    fn shuffle_position(&self) -> Option<usize> {
+        let current = self.current_index?;
+        self.shuffle_order
+            .iter()
+            .position(|index| *index == current)
+    }
     pub fn retrieve_queue(&mut self) -> color_eyre::Result<()> {
         if fs::exists(&self.queuelist_path)? {
             let queuelist = fs::read_to_string(&self.queuelist_path)?;
             self.queuelist = serde_json::from_str(queuelist.as_str())?;
         }
+        if fs::exists(&self.queuemodes_path)? {
+            let modes = fs::read_to_string(&self.queuemodes_path)?;
+            self.modes = serde_json::from_str(modes.as_str())?;
+        }
+        if self.modes.shuffle {
+            self.reshuffle(self.current_index);
+        }
         Ok(())
     }
     pub fn save_queue(&self) -> color_eyre::Result<()> {
@@ -55,6 +350,8 @@ impl Queue {
         }
         let queuelist_json = serde_json::to_string_pretty(&self.queuelist)?;
         fs::write(self.queuelist_path.clone(), queuelist_json)?;
+        let queuemodes_json = serde_json::to_string_pretty(&self.modes)?;
+        fs::write(self.queuemodes_path.clone(), queuemodes_json)?;
         Ok(())
     }
 
@@ -64,11 +361,23 @@ impl Queue {
     pub fn queuelist_state(&mut self) -> &mut ListState {
         &mut self.queuelist_state
     }
-    fn init_queuelist_path() -> String {
+    pub fn current_index(&self) -> Option<usize> {
+        self.current_index
+    }
+    /// Keeps the cursor on an existing entry after the queue shrank.
+    pub fn clamp_selection(&mut self) {
+        let last = self.queuelist.len().saturating_sub(1);
+        if let Some(selected) = self.queuelist_state.selected()
+            && selected > last
+        {
+            self.queuelist_state.select(Some(last));
+        }
+    }
+    fn init_queue_path(filename: &str) -> String {
         match dirs::data_local_dir() {
             Some(mut path) => {
                 path.push("ymp");
-                path.push("queuelist.json");
+                path.push(filename);
                 path.to_string_lossy().into_owned()
             }
             None => {
@@ -78,3 +387,175 @@ impl Queue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_of(ids: &[&str]) -> Queue {
+        let mut queue = Queue::new();
+        for id in ids {
+            queue.push(Video {
+                id: id.to_string(),
+                ..Default::default()
+            });
+        }
+        queue
+    }
+
+    fn ids(queue: &Queue) -> Vec<&str> {
+        queue
+            .queuelist
+            .iter()
+            .map(|video| video.id.as_str())
+            .collect()
+    }
+
+    fn current(queue: &Queue) -> Option<&str> {
+        queue
+            .current_index
+            .map(|index| queue.queuelist[index].id.as_str())
+    }
+
+    /// Plays the rest of the queue, checking the shuffle order stays a permutation of it.
+    fn rest(queue: &mut Queue) -> Vec<String> {
+        let mut order: Vec<usize> = queue.shuffle_order.clone();
+        order.sort_unstable();
+        if queue.modes.shuffle {
+            assert_eq!(order, (0..queue.queuelist.len()).collect::<Vec<_>>());
+        }
+        std::iter::from_fn(|| queue.play_next().map(|video| video.id)).collect()
+    }
+
+    #[test]
+    fn remove_keeps_the_current_entry() {
+        let mut queue = queue_of(&["a", "b", "c", "d"]);
+        queue.play_index(2).unwrap();
+        queue.remove(0).unwrap();
+        assert_eq!(ids(&queue), ["b", "c", "d"]);
+        assert_eq!(current(&queue), Some("c"));
+        queue.remove(2).unwrap();
+        assert_eq!(current(&queue), Some("c"));
+        queue.remove(1).unwrap();
+        assert_eq!(current(&queue), None);
+        assert!(queue.remove(5).is_err());
+    }
+
+    #[test]
+    fn move_entry_follows_the_current_entry() {
+        let mut queue = queue_of(&["a", "b", "c", "d"]);
+        queue.play_index(1).unwrap();
+        queue.move_entry(0, 3).unwrap();
+        assert_eq!(ids(&queue), ["b", "c", "d", "a"]);
+        assert_eq!(current(&queue), Some("b"));
+        queue.move_entry(0, 2).unwrap();
+        assert_eq!(ids(&queue), ["c", "d", "b", "a"]);
+        assert_eq!(current(&queue), Some("b"));
+        queue.move_entry(3, 0).unwrap();
+        assert_eq!(ids(&queue), ["a", "c", "d", "b"]);
+        assert_eq!(rest(&mut queue), Vec::<String>::new());
+    }
+
+    #[test]
+    fn play_next_from_puts_the_entry_after_the_current_one() {
+        let mut queue = queue_of(&["a", "b", "c", "d", "e"]);
+        assert_eq!(queue.play_next_from(3).unwrap(), 0);
+        assert_eq!(ids(&queue), ["d", "a", "b", "c", "e"]);
+        queue.play_index(2).unwrap();
+        assert_eq!(queue.play_next_from(4).unwrap(), 3);
+        assert_eq!(queue.play_next_from(0).unwrap(), 2);
+        assert_eq!(ids(&queue), ["a", "b", "d", "e", "c"]);
+        assert_eq!(current(&queue), Some("b"));
+        assert_eq!(queue.play_next_from(1).unwrap(), 1);
+        assert_eq!(rest(&mut queue), ["d", "e", "c"]);
+    }
+
+    #[test]
+    fn remove_duplicates_keeps_the_current_copy_and_undoes() {
+        let mut queue = queue_of(&["a", "b", "a", "c", "b"]);
+        queue.play_index(2).unwrap();
+        assert_eq!(queue.remove_duplicates(), 2);
+        assert_eq!(ids(&queue), ["b", "a", "c"]);
+        assert_eq!(queue.current_index, Some(1));
+        assert_eq!(queue.remove_duplicates(), 0);
+        assert!(queue.undo());
+        assert_eq!(ids(&queue), ["a", "b", "a", "c", "b"]);
+        assert_eq!(queue.current_index, Some(2));
+        assert!(!queue.undo());
+    }
+
+    #[test]
+    fn undo_finds_the_entry_playing_now() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.play_index(0).unwrap();
+        queue.clear();
+        assert!(queue.undo());
+        assert_eq!(current(&queue), None);
+        queue.remove(0).unwrap();
+        queue.play_index(1).unwrap();
+        assert!(queue.undo());
+        assert_eq!(ids(&queue), ["a", "b", "c"]);
+        assert_eq!(current(&queue), Some("c"));
+    }
+
+    #[test]
+    fn adding_or_moving_entries_drops_the_undo() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.remove(0).unwrap();
+        queue.push(Video {
+            id: "d".to_string(),
+            ..Default::default()
+        });
+        assert!(!queue.undo());
+        assert_eq!(ids(&queue), ["b", "c", "d"]);
+        queue.remove(0).unwrap();
+        queue.move_entry(1, 0).unwrap();
+        assert!(!queue.undo());
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.remove(0).unwrap();
+        queue.play_next_from(1).unwrap();
+        assert_eq!(ids(&queue), ["c", "b"]);
+        assert!(!queue.undo());
+    }
+
+    #[test]
+    fn shuffle_order_follows_edits() {
+        for _ in 0..20 {
+            let mut queue = queue_of(&["a", "b", "c", "d", "e", "f"]);
+            queue.toggle_shuffle();
+            queue.play_index(2).unwrap();
+            assert_eq!(queue.shuffle_order[0], 2);
+            queue.remove(4).unwrap();
+            queue.move_entry(0, 3).unwrap();
+            queue.push(Video {
+                id: "g".to_string(),
+                ..Default::default()
+            });
+            let next = queue.play_next_from(0).unwrap();
+            let next_id = queue.queuelist[next].id.clone();
+            assert_eq!(current(&queue), Some("c"));
+            let mut rest = rest(&mut queue);
+            assert_eq!(rest[0], next_id);
+            rest.sort();
+            assert_eq!(rest, ["a", "b", "d", "f", "g"]);
+        }
+    }
+
+    #[test]
+    fn shuffle_survives_duplicates_and_undo() {
+        let mut queue = queue_of(&["a", "b", "a", "c", "b", "d"]);
+        queue.toggle_shuffle();
+        queue.play_index(4).unwrap();
+        assert_eq!(queue.remove_duplicates(), 2);
+        assert_eq!(ids(&queue), ["a", "c", "b", "d"]);
+        assert_eq!(queue.shuffle_order[0], 2);
+        let mut order = queue.shuffle_order.clone();
+        order.sort_unstable();
+        assert_eq!(order, [0, 1, 2, 3]);
+        assert!(queue.undo());
+        assert_eq!(queue.shuffle_order[0], 4);
+        let mut played = rest(&mut queue);
+        played.sort();
+        assert_eq!(played, ["a", "a", "b", "c", "d"]);
+    }
+}
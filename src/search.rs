@@ -1,21 +1,64 @@
-use crate::types::Video;
-use std::path::PathBuf;
-use tokio::sync::mpsc;
-use yt_dlp::extractor::Youtube;
+use crate::search_filters::SearchFilters;
+use crate::settings::{Cookies, Settings};
+use crate::types::{LiveStatus, ResultKind, Video};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{path::PathBuf, process::Stdio, time::Instant};
+use tokio::{process::Command, sync::mpsc, task::JoinHandle};
 
-#[derive(Default, Debug)]
+/// A finished search, tagged with the generation of the search that produced it.
+type SearchResult = (u64, color_eyre::Result<SearchPage>);
+
+/// Filtered pages that can come back empty in a row before the search gives up on finding more.
+const MAX_EMPTY_PAGES: usize = 4;
+
+/// One page of search results.
+#[derive(Debug)]
+pub struct SearchPage {
+    pub videos: Vec<Video>,
+    /// Whether this is the first page of a new search, rather than more results for the last one.
+    pub is_first: bool,
+    /// How many results yt-dlp found for the page, before filtering.
+    fetched: usize,
+}
+
+#[derive(Debug)]
 pub struct Search {
     // tokio  search-related stuff
-    search_is_loading: bool, // In-case I want to add a leading screen
-    search_rx: Option<mpsc::UnboundedReceiver<color_eyre::Result<Vec<Video>>>>, //receives search results
+    /// Task running yt-dlp for the latest search. Aborting it kills yt-dlp too.
+    search_task: Option<JoinHandle<()>>,
+    search_tx: mpsc::UnboundedSender<SearchResult>,
+    search_rx: mpsc::UnboundedReceiver<SearchResult>, //receives search results
+    /// Bumped by every search and cancel, so results of older searches are thrown away.
+    search_generation: u64,
+    /// When the running search started, for the spinner.
+    search_started: Instant,
     yt_dlp_path: PathBuf,
     search_query: String,
+    /// The query without its filters.
+    search_text: String,
+    search_filters: SearchFilters,
+    /// URL of the channel or playlist being listed instead of searching.
+    search_listing: Option<String>,
+    /// Number of results asked for per page.
+    search_results: usize,
+    /// Results of the current query fetched so far, or being fetched by the running search.
+    search_loaded: usize,
+    /// The last page came back short, so there is nothing more to load.
+    search_exhausted: bool,
+    /// Results shown for the current query so far.
+    search_shown: usize,
+    /// Pages in a row that had nothing left after filtering.
+    search_empty_pages: usize,
+    cookies: Option<Cookies>,
 }
 
 impl Search {
     pub fn default() -> Self {
-        let search_is_loading = false;
-        let search_rx = None;
+        let search_task = None;
+        let (search_tx, search_rx) = mpsc::unbounded_channel();
+        let search_generation = 0;
+        let search_started = Instant::now();
         // Paths
         // tries to find yt-dlp path e.g. /usr/bin/yt-dlp
         // FIX: Install yt_dlp if path not found. HINT: Change ERR()
@@ -25,91 +68,331 @@ impl Search {
             Err(_) => PathBuf::from("/usr/bin/yt-dlp"),
         };
         let search_query = String::new();
+        let search_text = String::new();
+        let search_filters = SearchFilters::default();
+        let search_listing = None;
+        let search_results = 25;
+        let search_loaded = 0;
+        let search_exhausted = false;
+        let search_shown = 0;
+        let search_empty_pages = 0;
+        let cookies = None;
         Self {
-            search_is_loading,
+            search_task,
+            search_tx,
             search_rx,
+            search_generation,
+            search_started,
             yt_dlp_path,
             search_query,
+            search_text,
+            search_filters,
+            search_listing,
+            search_results,
+            search_loaded,
+            search_exhausted,
+            search_shown,
+            search_empty_pages,
+            cookies,
+        }
+    }
+
+    pub fn new(settings: &Settings) -> Self {
+        let search = Self::default();
+        Self {
+            yt_dlp_path: settings
+                .yt_dlp_path()
+                .cloned()
+                .unwrap_or(search.yt_dlp_path.clone()),
+            search_results: settings.search_results(),
+            cookies: settings.cookies(),
+            ..search
         }
     }
 
-    pub fn new() -> Self {
-        Self::default()
+    /// Starts searching for `search_query`, replacing any search still running. Fails if its
+    /// filters don't make sense, leaving the last search be.
+    pub fn search(
+        &mut self,
+        resultlist: &mut Vec<Video>,
+        search_query: String,
+    ) -> color_eyre::Result<()> {
+        let (search_text, search_filters) = SearchFilters::parse(&search_query)?;
+        self.start(resultlist, search_query, search_filters, None);
+        self.search_text = search_text;
+        self.fetch_page();
+        Ok(())
     }
 
-    pub fn search(&mut self, resultlist: &mut Vec<Video>, search_query: String) {
-        self.search_is_loading = true;
+    /// Lists the uploads of a channel or the entries of a playlist in place of the results.
+    pub fn open(&mut self, resultlist: &mut Vec<Video>, result: &Video) {
+        let listing = match result.kind {
+            ResultKind::Channel => format!("{}/videos", result.url()),
+            ResultKind::Video | ResultKind::Playlist => result.url(),
+        };
+        self.start(
+            resultlist,
+            result.title.clone(),
+            SearchFilters::default(),
+            Some(listing),
+        );
+        self.fetch_page();
+    }
 
+    fn start(
+        &mut self,
+        resultlist: &mut Vec<Video>,
+        search_query: String,
+        search_filters: SearchFilters,
+        search_listing: Option<String>,
+    ) {
+        self.cancel();
         resultlist.clear();
-        let (tx, rx) = mpsc::unbounded_channel();
-        self.search_rx = Some(rx);
 
-        self.search_query = search_query.clone();
+        self.search_query = search_query;
+        self.search_text = String::new();
+        self.search_filters = search_filters;
+        self.search_listing = search_listing;
+        self.search_loaded = 0;
+        self.search_exhausted = false;
+        self.search_shown = 0;
+        self.search_empty_pages = 0;
+    }
+
+    /// Fetches the next page of results for the last query, unless it's already on its way or
+    /// there are no more. Returns whether it started.
+    pub fn load_more(&mut self) -> bool {
+        if self.is_loading() || self.search_exhausted || self.search_query.is_empty() {
+            return false;
+        }
+        self.fetch_page();
+        true
+    }
+
+    fn fetch_page(&mut self) {
+        self.search_started = Instant::now();
+        let generation = self.search_generation;
+        let tx = self.search_tx.clone();
         let yt_dlp_path = self.yt_dlp_path.clone();
+        let search_query = self.search_query.clone();
+        let search_filters = self.search_filters.clone();
+        let cookies = self.cookies.clone();
+        // yt-dlp counts playlist items from 1
+        let first = self.search_loaded + 1;
+        let last = self.search_loaded + self.search_results;
+        self.search_loaded = last;
+        let target = match &self.search_listing {
+            Some(listing) => listing.clone(),
+            None => self.search_filters.target(&self.search_text, last),
+        };
 
-        tokio::spawn(async move {
-            let out = perform_search(yt_dlp_path, search_query).await;
-            let _ = tx.send(out);
-        });
+        self.search_task = Some(tokio::spawn(async move {
+            log::info!("Searching for {search_query:?} (results {first} to {last})");
+            let out = perform_search(yt_dlp_path, target, first, last, cookies)
+                .await
+                .map(|videos| SearchPage {
+                    fetched: videos.len(),
+                    videos: videos
+                        .into_iter()
+                        .filter(|video| search_filters.matches(video))
+                        .collect(),
+                    is_first: false,
+                });
+            if let Ok(page) = &out {
+                log::info!(
+                    "Search found {} videos, {} after filtering",
+                    page.fetched,
+                    page.videos.len()
+                );
+            }
+            let _ = tx.send((generation, out));
+        }));
+    }
 
-        // self.search_is_loading is set to false in check_search_results for obvious reasons. (Because
-        // search_is_loading doesn't stop until check search results is completed)
+    /// Stops the running search, if any. Returns whether there was one.
+    pub fn cancel(&mut self) -> bool {
+        let Some(task) = self.search_task.take() else {
+            return false;
+        };
+        // a page that already came back but wasn't picked up yet is thrown away too
+        self.search_generation += 1;
+        // its page never arrives, and more isn't loaded by itself until the next search
+        self.search_loaded = self.search_loaded.saturating_sub(self.search_results);
+        self.search_exhausted = true;
+        task.abort();
+        log::info!("Cancelled the search for {:?}", self.search_query);
+        true
     }
 
-    pub fn check_search_results(&mut self) -> std::io::Result<Vec<Video>> {
-        if let Some(rx) = &mut self.search_rx {
-            match rx.try_recv() {
-                Ok(Ok(videos)) => {
-                    self.search_is_loading = false;
-                    self.search_rx = None;
-                    return Ok(videos);
-                }
-                Ok(Err(e)) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!("Search returned no videos. Error: {}", e),
-                    ));
-                }
-                Err(mpsc::error::TryRecvError::Empty) => {
-                    // Might still be loading. Don't do anything here.
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::NotFound,
-                        "Search came back Empty. Not an issue.",
-                    ));
-                }
-                Err(mpsc::error::TryRecvError::Disconnected) => {
-                    // Died unexpectedly
-                    self.search_is_loading = false;
-                    self.search_rx = None;
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "Search failed. Receiver Disconnected.",
-                    ));
+    pub fn is_loading(&self) -> bool {
+        self.search_task.is_some()
+    }
+
+    /// Whether the running search is for more results of a query that already has some.
+    pub fn is_loading_more(&self) -> bool {
+        self.is_loading() && self.search_loaded > self.search_results
+    }
+
+    /// Whether the results are the videos of a channel or playlist rather than a search.
+    pub fn is_listing(&self) -> bool {
+        self.search_listing.is_some()
+    }
+
+    /// Whether every result of the current query has been loaded, or no more will be.
+    pub fn is_exhausted(&self) -> bool {
+        self.search_exhausted
+    }
+
+    pub fn search_query(&self) -> &str {
+        &self.search_query
+    }
+
+    /// How long the running search has been going.
+    pub fn search_started(&self) -> Instant {
+        self.search_started
+    }
+
+    /// The outcome of the latest search once it's done, `None` while it's still going or if there
+    /// is no search.
+    pub fn check_search_results(&mut self) -> Option<color_eyre::Result<SearchPage>> {
+        // Might still be loading. Don't do anything here.
+        while let Ok((generation, mut result)) = self.search_rx.try_recv() {
+            // a search that was cancelled or replaced can still have finished in the meantime
+            if generation != self.search_generation {
+                continue;
+            }
+            self.search_task = None;
+            let Ok(page) = &mut result else {
+                // a failed page isn't retried by itself; searching again starts over
+                self.search_exhausted = true;
+                return Some(result);
+            };
+            self.search_exhausted = page.fetched < self.search_results;
+            // the filters may have thrown out the whole page, so nothing would scroll to the end
+            // of the list to ask for the next one
+            if page.videos.is_empty() && !self.search_exhausted {
+                self.search_empty_pages += 1;
+                if self.search_empty_pages < MAX_EMPTY_PAGES {
+                    self.fetch_page();
+                    return None;
                 }
+                self.search_exhausted = true;
             }
+            self.search_empty_pages = 0;
+            page.is_first = self.search_shown == 0;
+            self.search_shown += page.videos.len();
+            return Some(result);
         }
-        Err(std::io::Error::other(format!(
-            "Unidentified Error: {}: line: {}",
-            file!(),
-            line!()
-        )))
+        None
     }
 }
+
+/// Runs yt-dlp for the `first` to the `last` result, counting from 1, of `target`: a `ytsearch`
+/// query or a search URL. yt-dlp is killed if the future is dropped, e.g. when its task is aborted.
+///
+/// The binary is run directly rather than through the `yt-dlp` crate, so a search can be stopped
+/// by killing it and pages, search URLs and cookies use yt-dlp's own options (cookies through
+/// [`Cookies::ytdl_option`]).
 async fn perform_search(
     yt_dlp_path: PathBuf,
-    search_query: String,
+    target: String,
+    first: usize,
+    last: usize,
+    cookies: Option<Cookies>,
 ) -> color_eyre::Result<Vec<Video>> {
-    let extractor = Youtube::new(yt_dlp_path);
-    let options = extractor.search(&search_query, 25).await?;
-    let mut videos: Vec<Video> = Vec::new();
-    let mut v: Video = Video::default();
-    for entry in options.entries {
-        v.id = entry.id;
-        v.title = entry.title;
-        if let Some(a) = entry.uploader {
-            v.uploader = a;
-        };
-        videos.push(v.clone());
+    let mut command = Command::new(&yt_dlp_path);
+    command
+        .arg("--no-progress")
+        .arg("--dump-single-json")
+        .arg("--flat-playlist")
+        .arg(format!("--playlist-items={first}-{last}"));
+    if let Some(cookies) = cookies {
+        command.arg(format!("--{}", cookies.ytdl_option()));
+    }
+    command
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    log::info!("Starting {:?}", command.as_std());
+    let output = command
+        .output()
+        .await
+        .map_err(|e| color_eyre::eyre::eyre!("Could not start {}: {e}", yt_dlp_path.display()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // yt-dlp ends with the line that says what went wrong
+        let reason = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("no output");
+        return Err(color_eyre::eyre::eyre!(
+            "yt-dlp failed ({}): {reason}",
+            output.status
+        ));
     }
+    let playlist: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| color_eyre::eyre::eyre!("Could not read yt-dlp's output: {e}"))?;
+    let entries = match playlist.get("entries").and_then(Value::as_array) {
+        Some(entries) => entries.as_slice(),
+        // a single video has no entries, it's the entry itself
+        None if playlist.get("id").is_some() => std::slice::from_ref(&playlist),
+        None => &[],
+    };
+    let videos = entries
+        .iter()
+        .filter_map(|entry| {
+            let field = |name: &str| entry.get(name).and_then(Value::as_str);
+            // flat entries only have a list of thumbnails, the biggest one last
+            let thumbnail = field("thumbnail").or_else(|| {
+                entry
+                    .get("thumbnails")?
+                    .as_array()?
+                    .last()?
+                    .get("url")?
+                    .as_str()
+            });
+            let live_status = entry
+                .get("live_status")
+                .and_then(|status| LiveStatus::deserialize(status).ok())
+                .unwrap_or_else(|| {
+                    if entry.get("is_live").and_then(Value::as_bool) == Some(true) {
+                        LiveStatus::IsLive
+                    } else {
+                        LiveStatus::NotLive
+                    }
+                });
+            let url = field("url").unwrap_or_default();
+            // channels and playlists come from the tab extractor, videos from the video one
+            let kind = if url.contains("list=") {
+                ResultKind::Playlist
+            } else if field("ie_key") == Some("YoutubeTab") {
+                ResultKind::Channel
+            } else {
+                ResultKind::Video
+            };
+            Some(Video {
+                kind,
+                id: field("id")?.to_string(),
+                title: field("title").unwrap_or_default().to_string(),
+                uploader: field("uploader")
+                    .or_else(|| field("channel"))
+                    .unwrap_or_default()
+                    .to_string(),
+                duration: entry.get("duration").and_then(Value::as_f64),
+                channel_id: field("channel_id").unwrap_or_default().to_string(),
+                channel_url: field("channel_url")
+                    .or_else(|| field("uploader_url"))
+                    .unwrap_or_default()
+                    .to_string(),
+                view_count: entry.get("view_count").and_then(Value::as_u64),
+                upload_date: field("upload_date").unwrap_or_default().to_string(),
+                live_status,
+                thumbnail: thumbnail.unwrap_or_default().to_string(),
+            })
+        })
+        .collect();
     Ok(videos)
 }
@@ -0,0 +1,396 @@
+use crate::types::{LiveStatus, ResultKind, Video};
+
+/// Order YouTube returns results in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SortOrder {
+    #[default]
+    Relevance,
+    Date,
+    Views,
+}
+
+/// How recently a video was uploaded, as YouTube's own search filter offers it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadDate {
+    Hour,
+    Today,
+    Week,
+    Month,
+    Year,
+}
+
+/// Length of a video. YouTube knows the first three itself, other ranges are filtered here.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DurationFilter {
+    /// Under 4 minutes.
+    Short,
+    /// 4 to 20 minutes.
+    Medium,
+    /// Over 20 minutes.
+    Long,
+    /// Between two lengths in seconds, either end may be open.
+    Range(Option<f64>, Option<f64>),
+}
+
+impl DurationFilter {
+    fn bounds(&self) -> (Option<f64>, Option<f64>) {
+        match *self {
+            DurationFilter::Short => (None, Some(240.0)),
+            DurationFilter::Medium => (Some(240.0), Some(1200.0)),
+            DurationFilter::Long => (Some(1200.0), None),
+            DurationFilter::Range(min, max) => (min, max),
+        }
+    }
+}
+
+/// Filters typed into the search popup along with the query, like `lofi dur:>10m sort:views`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchFilters {
+    pub duration: Option<DurationFilter>,
+    pub uploaded: Option<UploadDate>,
+    pub music_only: bool,
+    pub exclude_live: bool,
+    pub sort: SortOrder,
+    /// Whether to look for videos, channels or playlists.
+    pub kind: ResultKind,
+}
+
+impl SearchFilters {
+    /// Takes the filters out of `query`, returning what's left to search for along with them.
+    /// Words that only look like filters (`re:zero`) stay in the query.
+    pub fn parse(query: &str) -> color_eyre::Result<(String, Self)> {
+        let mut filters = Self::default();
+        let mut words = Vec::new();
+        for word in query.split_whitespace() {
+            let Some((key, value)) = word.split_once(':') else {
+                words.push(word);
+                continue;
+            };
+            let value = value.to_lowercase();
+            match key.to_lowercase().as_str() {
+                "dur" => filters.duration = Some(parse_duration_filter(&value)?),
+                "date" => {
+                    filters.uploaded = Some(match value.as_str() {
+                        "hour" => UploadDate::Hour,
+                        "today" => UploadDate::Today,
+                        "week" => UploadDate::Week,
+                        "month" => UploadDate::Month,
+                        "year" => UploadDate::Year,
+                        _ => {
+                            return Err(color_eyre::eyre::eyre!(
+                                "Unknown upload date `{value}`, use hour, today, week, month or year"
+                            ));
+                        }
+                    })
+                }
+                "sort" => {
+                    filters.sort = match value.as_str() {
+                        "relevance" => SortOrder::Relevance,
+                        "date" => SortOrder::Date,
+                        "views" => SortOrder::Views,
+                        _ => {
+                            return Err(color_eyre::eyre::eyre!(
+                                "Unknown sort order `{value}`, use relevance, date or views"
+                            ));
+                        }
+                    }
+                }
+                "type" => {
+                    filters.kind = match value.as_str() {
+                        "video" => ResultKind::Video,
+                        "channel" => ResultKind::Channel,
+                        "playlist" => ResultKind::Playlist,
+                        _ => {
+                            return Err(color_eyre::eyre::eyre!(
+                                "Unknown result type `{value}`, use video, channel or playlist"
+                            ));
+                        }
+                    }
+                }
+                "only" if value == "music" => filters.music_only = true,
+                "live" if value == "no" => filters.exclude_live = true,
+                "only" | "live" => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Unknown filter `{word}`, did you mean only:music or live:no?"
+                    ));
+                }
+                _ => words.push(word),
+            }
+        }
+        if filters.music_only
+            && (filters.uploaded.is_some() || filters.sort != SortOrder::Relevance)
+        {
+            return Err(color_eyre::eyre::eyre!(
+                "YouTube Music can't be filtered by upload date or sorted"
+            ));
+        }
+        if filters.kind != ResultKind::Video
+            && (filters.duration.is_some() || filters.exclude_live || filters.music_only)
+        {
+            return Err(color_eyre::eyre::eyre!(
+                "Duration, live and music filters only work when searching for videos"
+            ));
+        }
+        let text = words.join(" ");
+        if text.is_empty() {
+            return Err(color_eyre::eyre::eyre!("Nothing to search for"));
+        }
+        Ok((text, filters))
+    }
+
+    /// What yt-dlp is asked for to get results `1..=last` of a search for `text`. YouTube applies
+    /// what it can of the filters, [`SearchFilters::matches`] takes care of the rest.
+    pub fn target(&self, text: &str, last: usize) -> String {
+        if self.music_only {
+            return format!(
+                "https://music.youtube.com/search?q={}#songs",
+                url_encode(text)
+            );
+        }
+        let duration = match self.duration {
+            Some(DurationFilter::Short) => Some(1),
+            Some(DurationFilter::Long) => Some(2),
+            Some(DurationFilter::Medium) => Some(3),
+            Some(DurationFilter::Range(..)) | None => None,
+        };
+        if self.uploaded.is_none()
+            && self.sort == SortOrder::Relevance
+            && duration.is_none()
+            && self.kind == ResultKind::Video
+        {
+            return format!("ytsearch{last}:{text}");
+        }
+        // `sp` is a base64 protobuf message: sort order in field 1, filters in field 2 with the
+        // upload date in field 1, the result type in 2 and the duration in 3
+        let mut sp = Vec::new();
+        match self.sort {
+            SortOrder::Relevance => {}
+            SortOrder::Date => sp.extend([0x08, 2]),
+            SortOrder::Views => sp.extend([0x08, 3]),
+        }
+        // sorting alone goes without filters, like YouTube's own links
+        if self.uploaded.is_some() || duration.is_some() || self.kind != ResultKind::Video {
+            let mut filters = Vec::new();
+            if let Some(uploaded) = self.uploaded {
+                let value = match uploaded {
+                    UploadDate::Hour => 1,
+                    UploadDate::Today => 2,
+                    UploadDate::Week => 3,
+                    UploadDate::Month => 4,
+                    UploadDate::Year => 5,
+                };
+                filters.extend([0x08, value]);
+            }
+            let kind = match self.kind {
+                ResultKind::Video => 1,
+                ResultKind::Channel => 2,
+                ResultKind::Playlist => 3,
+            };
+            filters.extend([0x10, kind]);
+            if let Some(duration) = duration {
+                filters.extend([0x18, duration]);
+            }
+            sp.extend([0x12, filters.len() as u8]);
+            sp.extend(filters);
+        }
+        format!(
+            "https://www.youtube.com/results?search_query={}&sp={}",
+            url_encode(text),
+            url_encode(&base64(&sp))
+        )
+    }
+
+    /// Whether `video` passes the filters YouTube couldn't apply itself.
+    pub fn matches(&self, video: &Video) -> bool {
+        if self.exclude_live
+            && matches!(
+                video.live_status,
+                LiveStatus::IsLive | LiveStatus::IsUpcoming
+            )
+        {
+            return false;
+        }
+        match self.duration.map(|duration| duration.bounds()) {
+            // streams with no length can't be said to be long or short
+            Some((min, max)) => video.duration.is_some_and(|duration| {
+                min.is_none_or(|min| duration >= min) && max.is_none_or(|max| duration <= max)
+            }),
+            None => true,
+        }
+    }
+}
+
+/// `short`, `medium`, `long`, `>10m`, `<5m` or `5m-20m`.
+fn parse_duration_filter(value: &str) -> color_eyre::Result<DurationFilter> {
+    let invalid = || {
+        color_eyre::eyre::eyre!(
+            "Unknown duration `{value}`, use short, medium, long, >10m, <5m or 5m-20m"
+        )
+    };
+    let length = |text: &str| parse_length(text).ok_or_else(invalid);
+    Ok(match value {
+        "short" => DurationFilter::Short,
+        "medium" => DurationFilter::Medium,
+        "long" => DurationFilter::Long,
+        _ => {
+            if let Some(min) = value.strip_prefix('>') {
+                DurationFilter::Range(Some(length(min)?), None)
+            } else if let Some(max) = value.strip_prefix('<') {
+                DurationFilter::Range(None, Some(length(max)?))
+            } else if let Some((min, max)) = value.split_once('-') {
+                DurationFilter::Range(Some(length(min)?), Some(length(max)?))
+            } else {
+                return Err(invalid());
+            }
+        }
+    })
+}
+
+/// A length like `90s`, `10m` or `1.5h` in seconds. Plain numbers are minutes.
+fn parse_length(text: &str) -> Option<f64> {
+    let (number, unit) = match text.char_indices().last()? {
+        (index, 's') => (&text[..index], 1.0),
+        (index, 'm') => (&text[..index], 60.0),
+        (index, 'h') => (&text[..index], 3600.0),
+        _ => (text, 60.0),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.0)
+        .map(|number| number * unit)
+}
+
+/// Percent-encodes everything but unreserved characters, for a URL's query string.
+fn url_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sp(query: &str) -> String {
+        let (text, filters) = SearchFilters::parse(query).unwrap();
+        let target = filters.target(&text, 25);
+        target
+            .split_once("&sp=")
+            .map(|(_, sp)| sp.to_string())
+            .unwrap_or(target)
+    }
+
+    #[test]
+    fn encodes_sp_like_youtube() {
+        assert_eq!(sp("lofi date:today"), "EgQIAhAB");
+        assert_eq!(sp("lofi sort:date"), "CAI%3D");
+        assert_eq!(sp("lofi dur:short"), "EgQQARgB");
+        assert_eq!(sp("lofi type:channel"), "EgIQAg%3D%3D");
+        assert_eq!(
+            sp("lofi sort:views date:week dur:long"),
+            "CAMSBggDEAEYAg%3D%3D"
+        );
+    }
+
+    #[test]
+    fn plain_searches_stay_ytsearch() {
+        assert_eq!(sp("lofi beats"), "ytsearch25:lofi beats");
+        assert_eq!(sp("lofi dur:>10m live:no"), "ytsearch25:lofi");
+        assert_eq!(
+            sp("lo fi only:music"),
+            "https://music.youtube.com/search?q=lo%20fi#songs"
+        );
+    }
+
+    #[test]
+    fn parses_duration_ranges() {
+        let duration = |query: &str| SearchFilters::parse(query).unwrap().1.duration;
+        assert_eq!(
+            duration("a dur:>10m"),
+            Some(DurationFilter::Range(Some(600.0), None))
+        );
+        assert_eq!(
+            duration("a dur:<90s"),
+            Some(DurationFilter::Range(None, Some(90.0)))
+        );
+        assert_eq!(
+            duration("a dur:5-1.5h"),
+            Some(DurationFilter::Range(Some(300.0), Some(5400.0)))
+        );
+        assert_eq!(duration("a dur:MEDIUM"), Some(DurationFilter::Medium));
+        assert!(SearchFilters::parse("a dur:>nan").is_err());
+        assert!(SearchFilters::parse("a dur:>").is_err());
+        assert!(SearchFilters::parse("a dur:10m").is_err());
+    }
+
+    #[test]
+    fn filters_by_duration_and_live_status() {
+        let (_, filters) = SearchFilters::parse("a dur:4m-20m live:no").unwrap();
+        let video = |duration, live_status| Video {
+            duration,
+            live_status,
+            ..Default::default()
+        };
+        assert!(filters.matches(&video(Some(600.0), LiveStatus::NotLive)));
+        assert!(!filters.matches(&video(Some(60.0), LiveStatus::NotLive)));
+        assert!(!filters.matches(&video(None, LiveStatus::NotLive)));
+        assert!(!filters.matches(&video(Some(600.0), LiveStatus::IsUpcoming)));
+    }
+
+    #[test]
+    fn keeps_words_that_only_look_like_filters() {
+        let (text, filters) = SearchFilters::parse("re:zero ost https://youtu.be/x").unwrap();
+        assert_eq!(text, "re:zero ost https://youtu.be/x");
+        assert_eq!(filters, SearchFilters::default());
+    }
+
+    #[test]
+    fn rejects_conflicting_filters() {
+        for query in [
+            "a only:music sort:date",
+            "a only:music date:week",
+            "a type:channel dur:long",
+            "a type:playlist live:no",
+            "a type:channel only:music",
+            "dur:long",
+            "a live:yes",
+            "a sort:rating",
+        ] {
+            assert!(SearchFilters::parse(query).is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn encodes_base64_and_urls() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+        assert_eq!(url_encode("a b&c=d/é~"), "a%20b%26c%3Dd%2F%C3%A9~");
+    }
+}
@@ -1,33 +1,408 @@
-use std::fs;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{MAIN_SEPARATOR, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use serde::Serialize;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Default)]
+use crate::types::PlaybackMode;
+use serde_json::{Map, Value};
+
+/// Prefix of the environment variables that override single settings, e.g. `YMP_SEEK_STEP=10`.
+const ENV_PREFIX: &str = "YMP_";
+
+/// Where yt-dlp and mpv get YouTube cookies from, for age-restricted and members-only videos.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cookies {
+    /// A browser to read them from, as yt-dlp's `--cookies-from-browser` takes it, e.g. `firefox`
+    /// or `chrome:Profile 1`.
+    Browser(String),
+    /// A Netscape format cookies.txt.
+    File(PathBuf),
+}
+
+impl Cookies {
+    /// The yt-dlp option, without its leading `--`, e.g. `cookies-from-browser=firefox`.
+    pub fn ytdl_option(&self) -> String {
+        match self {
+            Cookies::Browser(browser) => format!("cookies-from-browser={browser}"),
+            Cookies::File(path) => format!("cookies={}", path.display()),
+        }
+    }
+}
+
+/// mpv options for one [`PlaybackMode`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MpvModeSettings {
+    /// `--ytdl-format` to play with, e.g. `bestvideo[height<=1080]+bestaudio/best`. Audio mode
+    /// falls back to `bestaudio`.
+    pub ytdl_format: Option<String>,
+    /// Profile from mpv.conf to load.
+    pub profile: Option<String>,
+    /// Extra arguments, e.g. `["--volume=60"]`.
+    pub args: Vec<String>,
+}
+
+/// User settings, read from `settings.json`. Keys missing from the file take their default, unknown
+/// ones are an error so a typo doesn't go unnoticed.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default = "Settings::default", deny_unknown_fields)]
 pub struct Settings {
+    #[serde(skip)]
     settings_path: String,
+    /// File values of the settings overridden from the environment or command line, so they
+    /// aren't written back. `Value::Null` if the file didn't have the key.
+    #[serde(skip)]
+    overridden: Map<String, Value>,
+    /// Browser to read YouTube cookies from, e.g. `firefox` or `chrome:Profile 1`.
     browser: Option<String>,
+    /// cookies.txt to read YouTube cookies from, instead of a browser.
+    cookies: Option<PathBuf>,
+    /// Seconds skipped by a single relative seek.
+    seek_step: f64,
+    /// Percent added or taken off by a single volume change.
+    volume_step: f64,
+    /// Play the queue through one long-lived mpv playlist instead of one mpv per track.
+    gapless: bool,
+    /// mpv binary to play with. Looked up in `PATH` when unset.
+    mpv_path: Option<PathBuf>,
+    /// Extra arguments for every mpv ymp starts.
+    mpv_args: Vec<String>,
+    /// mpv options for audio mode.
+    audio: MpvModeSettings,
+    /// mpv options for video mode.
+    video: MpvModeSettings,
+    /// Number of results a search asks yt-dlp for.
+    search_results: usize,
+    /// yt-dlp binary to search with. Looked up in `PATH` when unset.
+    yt_dlp_path: Option<PathBuf>,
+    /// Most videos queued at once from a channel or playlist.
+    enqueue_all_max: usize,
+    /// Seconds an info notification stays up.
+    status_message_secs: f64,
+    /// Seconds a warning or error notification stays up. They stay in the log either way.
+    error_message_secs: f64,
+    /// How much goes into the log file: off, error, warn, info, debug or trace.
+    log_level: String,
+    /// Most play history entries kept, 0 for no limit.
+    history_max_entries: usize,
+    /// Days a play history entry is kept for, 0 to keep it forever.
+    history_max_age_days: u64,
+    /// Name of the color theme, see [`crate::theme::PRESETS`].
+    theme: String,
+    /// Single colors overriding the theme's, e.g. `"border": "#88c0d0"`.
+    colors: BTreeMap<String, String>,
+    /// Key bindings replacing the defaults, as context -> action -> keys. See [`crate::keymap`].
+    keybindings: BTreeMap<String, BTreeMap<String, Vec<String>>>,
 }
 
 impl Settings {
     pub fn default() -> Self {
         let settings_path = Self::init_settings_path();
+        let overridden = Map::new();
         let browser = Option::default();
+        let cookies = None;
+        let seek_step = 5.0;
+        let volume_step = 5.0;
+        let gapless = false;
+        let mpv_path = None;
+        let mpv_args = Vec::new();
+        let audio = MpvModeSettings::default();
+        let video = MpvModeSettings::default();
+        let search_results = 25;
+        let yt_dlp_path = None;
+        let enqueue_all_max = 200;
+        let status_message_secs = 2.0;
+        let error_message_secs = 6.0;
+        let log_level = String::from("info");
+        let history_max_entries = 1000;
+        let history_max_age_days = 0;
+        let theme = String::from("blue");
+        let colors = BTreeMap::new();
+        let keybindings = BTreeMap::new();
         Self {
             settings_path,
+            overridden,
             browser,
+            cookies,
+            seek_step,
+            volume_step,
+            gapless,
+            mpv_path,
+            mpv_args,
+            audio,
+            video,
+            search_results,
+            yt_dlp_path,
+            enqueue_all_max,
+            status_message_secs,
+            error_message_secs,
+            log_level,
+            history_max_entries,
+            history_max_age_days,
+            theme,
+            colors,
+            keybindings,
+        }
+    }
+    /// Reads the settings file, `path` or the default one, writing it with the defaults first if it
+    /// doesn't exist yet. `YMP_<KEY>` environment variables and then `overrides` (`key=value` pairs,
+    /// from `--set`) take precedence over the file but are never saved to it.
+    pub fn load(path: Option<PathBuf>, overrides: &[String]) -> color_eyre::Result<Self> {
+        let settings_path = match path.or_else(|| env::var_os("YMP_CONFIG").map(PathBuf::from)) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => Self::init_settings_path(),
+        };
+        let file_exists = fs::exists(&settings_path)?;
+        let file = if file_exists {
+            let contents = fs::read_to_string(&settings_path)?;
+            match serde_json::from_str::<Value>(&contents) {
+                Ok(Value::Object(file)) => file,
+                Ok(_) => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Settings file {settings_path} must hold a JSON object"
+                    ));
+                }
+                Err(e) => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Could not parse settings file {settings_path}: {e}"
+                    ));
+                }
+            }
+        } else {
+            Map::new()
+        };
+
+        let mut merged = file.clone();
+        // older versions saved the file's own path in it, it's never read back
+        merged.remove("settings_path");
+        let mut overridden = Map::new();
+        let keys = Self::keys()?;
+        for key in &keys {
+            if let Ok(value) = env::var(format!("{ENV_PREFIX}{}", key.to_uppercase())) {
+                Self::apply_override(&mut merged, &mut overridden, &file, key, &value)?;
+            }
+        }
+        for pair in overrides {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| color_eyre::eyre::eyre!("--set expects key=value, got {pair}"))?;
+            let key = key.trim().replace('-', "_");
+            if !keys.contains(&key) {
+                return Err(color_eyre::eyre::eyre!(
+                    "Unknown setting {key}. Known settings: {}",
+                    keys.join(", ")
+                ));
+            }
+            Self::apply_override(&mut merged, &mut overridden, &file, &key, value)?;
+        }
+
+        let mut settings: Settings = serde_json::from_value(Value::Object(merged))
+            .map_err(|e| color_eyre::eyre::eyre!("Invalid settings in {settings_path}: {e}"))?;
+        settings.settings_path = settings_path;
+        settings.overridden = overridden;
+        settings.validate()?;
+        if !file_exists {
+            settings.save()?;
+        }
+        Ok(settings)
+    }
+
+    pub fn cookies(&self) -> Option<Cookies> {
+        match (&self.browser, &self.cookies) {
+            (_, Some(path)) => Some(Cookies::File(path.clone())),
+            (Some(browser), None) => Some(Cookies::Browser(browser.clone())),
+            (None, None) => None,
+        }
+    }
+    pub fn seek_step(&self) -> f64 {
+        self.seek_step
+    }
+    pub fn volume_step(&self) -> f64 {
+        self.volume_step
+    }
+    pub fn gapless(&self) -> bool {
+        self.gapless
+    }
+    pub fn toggle_gapless(&mut self) {
+        self.gapless = !self.gapless;
+        // toggled by hand, so it's saved even if it came from an override
+        self.overridden.remove("gapless");
+    }
+    pub fn mpv_path(&self) -> PathBuf {
+        self.mpv_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("mpv"))
+    }
+    pub fn mpv_args(&self) -> &[String] {
+        &self.mpv_args
+    }
+    pub fn mpv_mode(&self, playback_mode: &PlaybackMode) -> &MpvModeSettings {
+        match playback_mode {
+            PlaybackMode::Audio => &self.audio,
+            PlaybackMode::Video => &self.video,
         }
     }
-    pub fn new() -> Self {
-        Self::default()
+    pub fn search_results(&self) -> usize {
+        self.search_results
+    }
+    pub fn yt_dlp_path(&self) -> Option<&PathBuf> {
+        self.yt_dlp_path.as_ref()
+    }
+    pub fn enqueue_all_max(&self) -> usize {
+        self.enqueue_all_max
+    }
+    pub fn status_message_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.status_message_secs)
+    }
+    pub fn error_message_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.error_message_secs)
+    }
+    pub fn log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::Info)
+    }
+    pub fn history_max_entries(&self) -> usize {
+        self.history_max_entries
+    }
+    pub fn history_max_age_days(&self) -> u64 {
+        self.history_max_age_days
+    }
+    pub fn theme(&self) -> &str {
+        &self.theme
+    }
+    pub fn set_theme(&mut self, theme: &str) {
+        self.theme = theme.to_string();
+        self.overridden.remove("theme");
+    }
+    pub fn colors(&self) -> &BTreeMap<String, String> {
+        &self.colors
+    }
+    pub fn keybindings(&self) -> &BTreeMap<String, BTreeMap<String, Vec<String>>> {
+        &self.keybindings
     }
     pub fn save(&self) -> color_eyre::Result<()> {
         if let Some((path, _filename)) = self.settings_path.rsplit_once("/") {
             fs::DirBuilder::new().recursive(true).create(path)?;
         }
-        let settings_json = serde_json::to_string_pretty(&self)?;
+        let mut settings = match serde_json::to_value(self)? {
+            Value::Object(settings) => settings,
+            _ => Map::new(),
+        };
+        // put back what the file said for anything overridden just for this run
+        for (key, value) in &self.overridden {
+            match value {
+                Value::Null => settings.remove(key),
+                value => settings.insert(key.clone(), value.clone()),
+            };
+        }
+        let settings_json = serde_json::to_string_pretty(&settings)?;
         fs::write(self.settings_path.clone(), settings_json)?;
         Ok(())
     }
+
+    /// Names of all settings, as they appear in the settings file.
+    fn keys() -> color_eyre::Result<Vec<String>> {
+        match serde_json::to_value(Self::default())? {
+            Value::Object(defaults) => Ok(defaults.keys().cloned().collect()),
+            _ => Ok(Vec::new()),
+        }
+    }
+    /// Sets `key` to `value` in `merged`. The value is read as JSON if it parses, e.g. `10` or
+    /// `true`, and as a plain string otherwise.
+    fn apply_override(
+        merged: &mut Map<String, Value>,
+        overridden: &mut Map<String, Value>,
+        file: &Map<String, Value>,
+        key: &str,
+        value: &str,
+    ) -> color_eyre::Result<()> {
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.into()));
+        // check it on its own so the error can name the setting
+        let mut single = Map::new();
+        single.insert(key.to_string(), value.clone());
+        serde_json::from_value::<Settings>(Value::Object(single))
+            .map_err(|e| color_eyre::eyre::eyre!("Invalid value for setting {key}: {e}"))?;
+        overridden
+            .entry(key)
+            .or_insert_with(|| file.get(key).cloned().unwrap_or(Value::Null));
+        merged.insert(key.to_string(), value);
+        Ok(())
+    }
+    fn validate(&self) -> color_eyre::Result<()> {
+        let positive = [
+            ("seek_step", self.seek_step),
+            ("volume_step", self.volume_step),
+            ("status_message_secs", self.status_message_secs),
+            ("error_message_secs", self.error_message_secs),
+        ];
+        for (key, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(color_eyre::eyre::eyre!(
+                    "Setting {key} must be a positive number, got {value}"
+                ));
+            }
+        }
+        if LevelFilter::from_str(&self.log_level).is_err() {
+            return Err(color_eyre::eyre::eyre!(
+                "Setting log_level must be off, error, warn, info, debug or trace, got {}",
+                self.log_level
+            ));
+        }
+        if self.browser.is_some() && self.cookies.is_some() {
+            return Err(color_eyre::eyre::eyre!(
+                "Set either browser or cookies, not both"
+            ));
+        }
+        if let Some(cookies) = &self.cookies
+            && !cookies.is_file()
+        {
+            return Err(color_eyre::eyre::eyre!(
+                "Cookies file {} doesn't exist",
+                cookies.display()
+            ));
+        }
+        match &self.mpv_path {
+            // a bare name like "mpv.sh" is looked up in PATH, as running it does
+            Some(path)
+                if !path.to_string_lossy().contains(MAIN_SEPARATOR)
+                    && which::which(path).is_err() =>
+            {
+                return Err(color_eyre::eyre::eyre!(
+                    "mpv_path {} wasn't found in PATH",
+                    path.display()
+                ));
+            }
+            Some(path) if path.to_string_lossy().contains(MAIN_SEPARATOR) && !path.is_file() => {
+                return Err(color_eyre::eyre::eyre!(
+                    "mpv_path {} doesn't exist",
+                    path.display()
+                ));
+            }
+            Some(_) => {}
+            None if which::which("mpv").is_err() => {
+                return Err(color_eyre::eyre::eyre!(
+                    "mpv wasn't found in PATH. Install it (https://mpv.io/installation/) or set mpv_path in the settings"
+                ));
+            }
+            None => {}
+        }
+        if self.search_results == 0 {
+            return Err(color_eyre::eyre::eyre!(
+                "Setting search_results must be at least 1"
+            ));
+        }
+        if self.enqueue_all_max == 0 {
+            return Err(color_eyre::eyre::eyre!(
+                "Setting enqueue_all_max must be at least 1"
+            ));
+        }
+        Ok(())
+    }
     fn init_settings_path() -> String {
         match dirs::data_local_dir() {
             Some(mut path) => {
@@ -42,3 +417,52 @@ impl Settings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_with(name: &str, contents: &str, mpv_path: &str) -> color_eyre::Result<Settings> {
+        let path = env::temp_dir().join(format!("ymp-{}-{name}.json", std::process::id()));
+        fs::write(&path, contents)?;
+        let settings = Settings::load(Some(path.clone()), &[format!("mpv_path={mpv_path}")]);
+        fs::remove_file(&path)?;
+        settings
+    }
+
+    fn load(name: &str, contents: &str) -> color_eyre::Result<Settings> {
+        // mpv isn't necessarily installed where the tests run
+        load_with(name, contents, "/bin/sh")
+    }
+
+    #[test]
+    fn loads_settings_written_by_older_versions() {
+        let settings = load(
+            "legacy",
+            r#"{
+  "settings_path": "/home/user/.local/share/ymp/settings.json",
+  "browser": null
+}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.seek_step(), 5.0);
+        assert!(settings.cookies().is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        let error = load("unknown", r#"{"seek_stp": 3}"#).unwrap_err();
+        assert!(error.to_string().contains("seek_stp"), "{error}");
+        assert!(load("unknown-mode", r#"{"audio": {"profle": "x"}}"#).is_err());
+    }
+
+    #[test]
+    fn looks_up_bare_mpv_names_in_path() {
+        let settings = load_with("bare", "{}", "sh").unwrap();
+        assert_eq!(settings.mpv_path(), PathBuf::from("sh"));
+        let error = load_with("missing", "{}", "no-such-mpv-here").unwrap_err();
+        assert!(error.to_string().contains("PATH"), "{error}");
+        let error = load_with("missing-path", "{}", "/no/such/mpv").unwrap_err();
+        assert!(error.to_string().contains("doesn't exist"), "{error}");
+    }
+}
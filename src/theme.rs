@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, env, str::FromStr};
+
+use ratatui::style::{Color, Style, palette::material};
+
+/// Names of the built-in themes, in the order the theme key cycles through them.
+pub const PRESETS: [&str; 7] = [
+    "blue", "green", "purple", "orange", "red", "terminal", "mono",
+];
+
+/// Colors the UI is drawn with. Built from a preset in the settings, with single colors
+/// overridden by the `colors` setting.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Theme {
+    name: String,
+    /// Borders, titles and most plain text.
+    border: Color,
+    /// Text of the entry under the cursor.
+    highlight_fg: Color,
+    /// Background of the entry under the cursor.
+    highlight_bg: Color,
+    /// Uploaders, track counts and other secondary text.
+    subtext: Color,
+    /// The filled part of the progress bar.
+    progress: Color,
+    /// Error notifications.
+    error: Color,
+    /// Warning notifications.
+    warning: Color,
+    /// No colors at all, only bold/dim/reversed text. Used for the "mono" theme and `NO_COLOR`.
+    monochrome: bool,
+}
+
+impl Theme {
+    /// Builds the theme `name` with `colors` (`border`, `highlight_fg`, `highlight_bg`, `subtext`,
+    /// `progress`, `error`, `warning`) on top. Colors are names (`red`, `lightblue`), `#rrggbb` or an ANSI
+    /// index (`0`-`255`). `NO_COLOR` in the environment forces the monochrome theme.
+    pub fn new(name: &str, colors: &BTreeMap<String, String>) -> color_eyre::Result<Self> {
+        if env::var_os("NO_COLOR").is_some_and(|no_color| !no_color.is_empty()) {
+            return Ok(Self::mono());
+        }
+        let mut theme = Self::preset(name).ok_or_else(|| {
+            color_eyre::eyre::eyre!("Unknown theme {name}. Themes: {}", PRESETS.join(", "))
+        })?;
+        if theme.monochrome {
+            return Ok(theme);
+        }
+        for (key, value) in colors {
+            let color = Color::from_str(value).map_err(|_| {
+                color_eyre::eyre::eyre!("Invalid color {value:?} for {key} in colors")
+            })?;
+            match key.as_str() {
+                "border" => theme.border = color,
+                "highlight_fg" => theme.highlight_fg = color,
+                "highlight_bg" => theme.highlight_bg = color,
+                "subtext" => theme.subtext = color,
+                "progress" => theme.progress = color,
+                "error" => theme.error = color,
+                "warning" => theme.warning = color,
+                _ => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Unknown color {key}. Colors: border, highlight_fg, highlight_bg, subtext, progress, error, warning"
+                    ));
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    fn preset(name: &str) -> Option<Self> {
+        let material = |palette: material::AccentedPalette| Self {
+            name: name.to_string(),
+            border: palette.a100,
+            highlight_fg: material::BLACK,
+            highlight_bg: palette.a100,
+            subtext: palette.c600,
+            progress: palette.a100,
+            error: material::RED.a200,
+            warning: material::AMBER.a200,
+            monochrome: false,
+        };
+        match name {
+            "blue" => Some(material(material::BLUE)),
+            "green" => Some(material(material::GREEN)),
+            "purple" => Some(material(material::DEEP_PURPLE)),
+            "orange" => Some(material(material::ORANGE)),
+            "red" => Some(Self {
+                error: material::YELLOW.a200,
+                warning: material::ORANGE.a200,
+                ..material(material::RED)
+            }),
+            // the terminal's own 16 colors, so it follows the terminal's color scheme
+            "terminal" => Some(Self {
+                name: name.to_string(),
+                border: Color::Cyan,
+                highlight_fg: Color::Black,
+                highlight_bg: Color::Cyan,
+                subtext: Color::Blue,
+                progress: Color::Cyan,
+                error: Color::Red,
+                warning: Color::Yellow,
+                monochrome: false,
+            }),
+            "mono" => Some(Self::mono()),
+            _ => None,
+        }
+    }
+
+    fn mono() -> Self {
+        Self {
+            name: String::from("mono"),
+            border: Color::Reset,
+            highlight_fg: Color::Reset,
+            highlight_bg: Color::Reset,
+            subtext: Color::Reset,
+            progress: Color::Reset,
+            error: Color::Reset,
+            warning: Color::Reset,
+            monochrome: true,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn border(&self) -> Style {
+        Style::new().fg(self.border)
+    }
+    pub fn highlight(&self) -> Style {
+        if self.monochrome {
+            Style::new().reversed()
+        } else {
+            Style::new().fg(self.highlight_fg).bg(self.highlight_bg)
+        }
+    }
+    pub fn subtext(&self) -> Style {
+        if self.monochrome {
+            Style::new().dim()
+        } else {
+            Style::new().fg(self.subtext)
+        }
+    }
+    pub fn progress_filled(&self) -> Style {
+        Style::new().fg(self.progress)
+    }
+    pub fn progress_unfilled(&self) -> Style {
+        if self.monochrome {
+            Style::new().dim()
+        } else {
+            Style::new().fg(self.subtext).dim()
+        }
+    }
+    pub fn error(&self) -> Style {
+        if self.monochrome {
+            Style::new().bold()
+        } else {
+            Style::new().fg(self.error)
+        }
+    }
+    pub fn warning(&self) -> Style {
+        if self.monochrome {
+            Style::new().bold()
+        } else {
+            Style::new().fg(self.warning)
+        }
+    }
+}
@@ -13,6 +13,19 @@ pub enum Mode {
     #[default]
     Default,
     Search,
+    Seek,
+    /// Typing the name of a new playlist.
+    NewPlaylist,
+    /// Typing a new name for the playlist under the cursor.
+    RenamePlaylist,
+    /// Typing the path of a playlist file to import.
+    Import,
+    /// Typing the path to export the queue or a playlist to.
+    Export,
+    /// Typing a filter for the History tab.
+    FilterHistory,
+    /// Choosing which playlist `App::playlist_candidate` goes into.
+    AddToPlaylist,
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -22,34 +35,57 @@ pub enum PlaybackMode {
     Video,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+impl RepeatMode {
+    pub fn cycle(&mut self) {
+        *self = match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub enum Screen {
     #[default]
     //Menu,
     Queue,
     Results,
+    Playlists,
+    History,
+    /// Past warnings and errors.
+    Log,
 }
 
 impl Screen {
+    /// Screens in the order of the header tabs.
+    const ALL: [Screen; 5] = [
+        Screen::Queue,
+        Screen::Results,
+        Screen::Playlists,
+        Screen::History,
+        Screen::Log,
+    ];
+
     pub fn next(&mut self) {
-        *self = match self {
-            Screen::Queue => Screen::Results,
-            Screen::Results => Screen::Queue,
-        }
+        self.select((self.current() + 1) % Self::ALL.len());
     }
 
     pub fn previous(&mut self) {
-        *self = match self {
-            Screen::Queue => Screen::Results,
-            Screen::Results => Screen::Queue,
-        }
+        self.select((self.current() + Self::ALL.len() - 1) % Self::ALL.len());
     }
 
     pub fn select(&mut self, index: usize) {
-        match index {
-            0 => *self = Screen::Queue,
-            1 => *self = Screen::Results,
-            _ => {}
+        if let Some(screen) = Self::ALL.get(index) {
+            *self = screen.clone();
         }
     }
 
@@ -57,6 +93,9 @@ impl Screen {
         match self {
             Screen::Queue => 0,
             Screen::Results => 1,
+            Screen::Playlists => 2,
+            Screen::History => 3,
+            Screen::Log => 4,
         }
     }
 }
@@ -67,7 +106,163 @@ pub struct Video {
     pub title: String,
     #[serde(default)]
     pub uploader: String,
-    // duration: f64,
+    /// Length in seconds, unknown for live streams and videos saved by older versions.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub channel_url: String,
+    #[serde(default)]
+    pub view_count: Option<u64>,
+    /// As yt-dlp gives it, e.g. `20240131`.
+    #[serde(default)]
+    pub upload_date: String,
+    #[serde(default)]
+    pub live_status: LiveStatus,
+    #[serde(default)]
+    pub thumbnail: String,
+    #[serde(default)]
+    pub kind: ResultKind,
+}
+
+impl Video {
+    pub fn url(&self) -> String {
+        match self.kind {
+            ResultKind::Video => format!("https://www.youtube.com/watch?v={}", self.id),
+            ResultKind::Channel if self.id.starts_with("UC") => {
+                format!("https://www.youtube.com/channel/{}", self.id)
+            }
+            // channels only known by their handle (`@name`) or an old `c/` or `user/` URL
+            ResultKind::Channel => format!("https://www.youtube.com/{}", self.id),
+            ResultKind::Playlist => format!("https://www.youtube.com/playlist?list={}", self.id),
+        }
+    }
+
+    /// The duration as `m:ss` or `h:mm:ss`, empty when it isn't known.
+    pub fn duration_label(&self) -> String {
+        self.duration.map(format_timestamp).unwrap_or_default()
+    }
+}
+
+/// What a search result is. Only videos end up in the queue, channels and playlists are opened
+/// to list what's in them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultKind {
+    #[default]
+    Video,
+    Channel,
+    Playlist,
+}
+
+impl ResultKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResultKind::Video => "video",
+            ResultKind::Channel => "channel",
+            ResultKind::Playlist => "playlist",
+        }
+    }
+}
+
+/// Pulls the video id out of the usual YouTube URL shapes (`watch?v=`, `youtu.be/`, `shorts/`,
+/// `embed/`, `live/`), or accepts a bare 11 character id.
+pub fn video_id_from_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let is_id = |id: &str| {
+        id.len() == 11
+            && id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    };
+    if is_id(url) {
+        return Some(url.to_string());
+    }
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (host, path) = without_scheme.split_once('/')?;
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+    let id = if host == "youtu.be" {
+        path.split(['?', '&', '#']).next()
+    } else if host == "youtube.com" || host == "music.youtube.com" {
+        if let Some(query) = path.strip_prefix("watch?") {
+            query
+                .split(['&', '#'])
+                .find_map(|parameter| parameter.strip_prefix("v="))
+        } else {
+            ["shorts/", "embed/", "live/"]
+                .iter()
+                .find_map(|prefix| path.strip_prefix(prefix))
+                .and_then(|rest| rest.split(['?', '&', '#', '/']).next())
+        }
+    } else {
+        None
+    }?;
+    is_id(id).then(|| id.to_string())
+}
+
+/// Makes out whether a YouTube URL is a video, a playlist or a channel. The [`Video`] only has the
+/// id and the URL as its title until yt-dlp looks it up. Bare ids aren't taken, they could as well
+/// be something to search for.
+pub fn result_from_url(url: &str) -> Option<Video> {
+    let url = url.trim();
+    if !url.contains('/') {
+        return None;
+    }
+    let result = |kind, id: &str| Video {
+        kind,
+        id: id.to_string(),
+        title: url.to_string(),
+        ..Default::default()
+    };
+    // a video out of a playlist is just the video
+    if let Some(id) = video_id_from_url(url) {
+        return Some(result(ResultKind::Video, &id));
+    }
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (host, path) = without_scheme.split_once('/')?;
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+    if host != "youtube.com" && host != "music.youtube.com" {
+        return None;
+    }
+    let path = path.split('#').next().unwrap_or_default();
+    if let Some(query) = path.strip_prefix("playlist?") {
+        let id = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("list="))?;
+        return Some(result(ResultKind::Playlist, id));
+    }
+    let mut segments = path.split(['?', '/']).filter(|segment| !segment.is_empty());
+    let id = match segments.next()? {
+        "channel" => segments.next()?.to_string(),
+        prefix @ ("c" | "user") => format!("{prefix}/{}", segments.next()?),
+        handle if handle.starts_with('@') => handle.to_string(),
+        _ => return None,
+    };
+    Some(result(ResultKind::Channel, &id))
+}
+
+/// Whether a video is a live stream, with the names yt-dlp uses for `live_status`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveStatus {
+    #[default]
+    NotLive,
+    IsLive,
+    IsUpcoming,
+    WasLive,
+    PostLive,
+}
+
+impl LiveStatus {
+    /// Badge shown in place of the duration, if any.
+    pub fn badge(&self) -> Option<&'static str> {
+        match self {
+            LiveStatus::IsLive => Some("LIVE"),
+            LiveStatus::IsUpcoming => Some("UPCOMING"),
+            LiveStatus::NotLive | LiveStatus::WasLive | LiveStatus::PostLive => None,
+        }
+    }
 }
 
 #[derive(Debug, Default, Setters)]
@@ -102,3 +297,101 @@ impl Widget for Popup<'_> {
             .render(area, buf);
     }
 }
+
+/// Formats seconds as `m:ss`, or `h:mm:ss` past an hour.
+pub fn format_timestamp(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_video_ids() {
+        for url in [
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?feature=share&v=dQw4w9WgXcQ&list=PL1",
+            "http://m.youtube.com/watch?v=dQw4w9WgXcQ#t=10",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+            "youtu.be/dQw4w9WgXcQ?t=3",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ?autoplay=1",
+            "https://www.youtube.com/live/dQw4w9WgXcQ/",
+        ] {
+            assert_eq!(
+                video_id_from_url(url).as_deref(),
+                Some("dQw4w9WgXcQ"),
+                "{url}"
+            );
+        }
+        for url in [
+            "https://www.youtube.com/watch?v=short",
+            "https://www.youtube.com/playlist?list=PLx",
+            "https://vimeo.com/watch?v=dQw4w9WgXcQ",
+            "dQw4w9WgXc!",
+            "",
+        ] {
+            assert_eq!(video_id_from_url(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn makes_out_result_kinds() {
+        let result = |url: &str| result_from_url(url).map(|video| (video.kind, video.id));
+        let expect = |kind, id: &str| Some((kind, id.to_string()));
+        assert_eq!(
+            result("https://www.youtube.com/@LofiGirl/videos"),
+            expect(ResultKind::Channel, "@LofiGirl")
+        );
+        assert_eq!(
+            result("youtube.com/c/LofiGirl"),
+            expect(ResultKind::Channel, "c/LofiGirl")
+        );
+        assert_eq!(
+            result("https://m.youtube.com/user/LofiGirl?sub=1"),
+            expect(ResultKind::Channel, "user/LofiGirl")
+        );
+        assert_eq!(
+            result("https://www.youtube.com/channel/UCSJ4gkVC6NrvII8umztf0Ow"),
+            expect(ResultKind::Channel, "UCSJ4gkVC6NrvII8umztf0Ow")
+        );
+        assert_eq!(
+            result("https://music.youtube.com/playlist?feature=share&list=PLabc#top"),
+            expect(ResultKind::Playlist, "PLabc")
+        );
+        assert_eq!(
+            result("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLabc"),
+            expect(ResultKind::Video, "dQw4w9WgXcQ")
+        );
+        for url in [
+            "dQw4w9WgXcQ",
+            "@LofiGirl",
+            "https://www.youtube.com/results?search_query=lofi",
+            "https://www.youtube.com/playlist?foo=bar",
+            "https://example.com/@LofiGirl",
+        ] {
+            assert_eq!(result(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn result_urls_point_back_at_youtube() {
+        for url in [
+            "https://www.youtube.com/@LofiGirl",
+            "https://www.youtube.com/c/LofiGirl",
+            "https://www.youtube.com/channel/UCSJ4gkVC6NrvII8umztf0Ow",
+            "https://www.youtube.com/playlist?list=PLabc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ] {
+            assert_eq!(result_from_url(url).unwrap().url(), url);
+        }
+    }
+}
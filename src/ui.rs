@@ -1,21 +1,27 @@
 use crate::App;
-use crate::types::{Mode, PlaybackMode, Popup, Screen, Video};
+use crate::history::{self, HistoryEntry};
+use crate::notifications::{Notification, Severity};
+use crate::player::Player;
+use crate::playlists::Playlist;
+use crate::theme::Theme;
+use crate::types::{
+    LiveStatus, Mode, PlaybackMode, Popup, RepeatMode, ResultKind, Screen, Video, format_timestamp,
+};
+
+use std::time::Duration;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use ratatui::{
     Frame,
     layout::{Constraint, Layout, Rect},
-    style::{Color, Style, Stylize, palette::material},
+    style::{Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Padding, Paragraph, Tabs},
+    widgets::{
+        Block, BorderType, Borders, Clear, LineGauge, List, ListItem, ListState, Padding,
+        Paragraph, Tabs,
+    },
 };
 
-// Color Scheme
-const COLOR_SCHEME: material::AccentedPalette = material::BLUE;
-const SUBTEXT_FG: Color = COLOR_SCHEME.c600;
-const HIGHLIGHT_FG: Color = material::BLACK;
-const HIGHLIGHT_BG: Color = COLOR_SCHEME.a100;
-const BORDER_FG: Color = COLOR_SCHEME.a100;
-
 impl App {
     /// Renders the user interface.
     pub fn render(&mut self, frame: &mut Frame) {
@@ -33,20 +39,85 @@ impl App {
             self.tabs_titles.clone(),
             frame,
             header_area,
+            &self.theme,
         );
 
-        render_status_bar(self.player.playback_mode().clone(), frame, status_area);
+        render_status_bar(
+            &self.player,
+            self.queue.repeat_mode(),
+            self.queue.is_shuffled(),
+            frame,
+            status_area,
+            &self.theme,
+        );
 
         match self.screen {
             Screen::Results => {
-                render_content(frame, content_area, self.resultlist_state, &self.resultlist);
+                render_content(
+                    frame,
+                    content_area,
+                    self.resultlist_state,
+                    &self.resultlist,
+                    None,
+                    &self.theme,
+                );
             }
             Screen::Queue => {
+                let current_index = self.queue.current_index();
                 render_content(
                     frame,
                     content_area,
                     self.queue.queuelist_state().to_owned(),
                     self.queue.queuelist(),
+                    current_index,
+                    &self.theme,
+                );
+            }
+            Screen::Playlists => {
+                let [playlists_area, videos_area] =
+                    Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                        .areas(content_area);
+                let playlists_state = self.playlists.playlists_state().to_owned();
+                render_playlists(
+                    frame,
+                    playlists_area,
+                    playlists_state,
+                    self.playlists.playlists(),
+                    &self.theme,
+                );
+                let videos = self
+                    .playlists
+                    .selected()
+                    .map(|playlist| playlist.videos.as_slice())
+                    .unwrap_or_default();
+                render_content(
+                    frame,
+                    videos_area,
+                    ListState::default(),
+                    videos,
+                    None,
+                    &self.theme,
+                );
+            }
+            Screen::History => {
+                let history_state = self.history.history_state().to_owned();
+                render_history(
+                    frame,
+                    content_area,
+                    history_state,
+                    &self.history.visible(),
+                    self.history.filter(),
+                    &self.theme,
+                );
+            }
+            Screen::Log => {
+                let log_state = self.notifications.log_state().to_owned();
+                render_log(
+                    frame,
+                    content_area,
+                    log_state,
+                    self.notifications.log(),
+                    &self.theme,
                 );
             }
         }
@@ -54,12 +125,93 @@ impl App {
         match self.mode {
             Mode::Default => {}
             Mode::Search => {
-                render_search(self.search_query.clone(), frame);
+                render_search(self.search_query.clone(), frame, &self.theme);
             }
+            Mode::Seek => {
+                render_prompt(
+                    " Seek to (m:ss, seconds or %) ",
+                    self.seek_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::NewPlaylist => {
+                render_prompt(
+                    " New Playlist ",
+                    self.playlist_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::RenamePlaylist => {
+                render_prompt(
+                    " Rename Playlist ",
+                    self.playlist_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::Import => {
+                render_prompt(
+                    " Import from (.m3u8, .xspf or URL list) ",
+                    self.path_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::Export => {
+                render_prompt(
+                    " Export to (.m3u8, .xspf or URL list) ",
+                    self.path_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::FilterHistory => {
+                render_prompt(
+                    " Filter History ",
+                    self.history_query.clone(),
+                    frame,
+                    &self.theme,
+                );
+            }
+            Mode::AddToPlaylist => {
+                render_playlist_picker(
+                    self.playlist_picker_state.to_owned(),
+                    self.playlists.playlists(),
+                    frame,
+                    &self.theme,
+                );
+            }
+        }
+
+        if self.search.is_loading() {
+            render_search_spinner(
+                self.search.search_query(),
+                self.search.is_loading_more(),
+                self.search.search_started().elapsed(),
+                frame,
+                content_area,
+                &self.theme,
+            );
         }
+        // on top of everything, just above the status bar
+        render_notifications(
+            self.notifications.toasts(),
+            frame,
+            content_area,
+            &self.theme,
+        );
     }
 }
-fn render_status_bar(playback_mode: PlaybackMode, frame: &mut Frame<'_>, status_area: Rect) {
+fn render_status_bar(
+    player: &Player,
+    repeat_mode: RepeatMode,
+    is_shuffled: bool,
+    frame: &mut Frame<'_>,
+    status_area: Rect,
+    theme: &Theme,
+) {
     // status_bar
     let [status_area_left, status_area_center, status_area_right] = Layout::horizontal([
         Constraint::Percentage(33),
@@ -69,7 +221,7 @@ fn render_status_bar(playback_mode: PlaybackMode, frame: &mut Frame<'_>, status_
     .areas(status_area);
 
     let block_border_type = BorderType::Rounded;
-    let block_border_style = Style::new().fg(BORDER_FG);
+    let block_border_style = theme.border();
     let (left_block, center_block, right_block) = (
         Block::new()
             .borders(Borders::LEFT | Borders::TOP | Borders::BOTTOM)
@@ -84,59 +236,92 @@ fn render_status_bar(playback_mode: PlaybackMode, frame: &mut Frame<'_>, status_
             .border_type(block_border_type)
             .border_style(block_border_style),
     );
-    match playback_mode {
-        PlaybackMode::Audio => {
-            frame.render_widget(
-                Paragraph::new(" Mode: [Audio] ")
-                    .left_aligned()
-                    .fg(BORDER_FG)
-                    .block(left_block),
-                status_area_left,
-            );
-        }
-        PlaybackMode::Video => {
-            frame.render_widget(
-                Paragraph::new(" Mode: [Video] ")
-                    .left_aligned()
-                    .fg(BORDER_FG)
-                    .block(left_block),
-                status_area_left,
-            );
-        }
-    }
-    frame.render_widget(
-        Paragraph::new("  ")
-            .left_aligned()
-            .fg(BORDER_FG)
-            .block(center_block.clone()),
-        status_area_center,
-    );
+    let playback_mode = match player.playback_mode() {
+        PlaybackMode::Audio => "Audio",
+        PlaybackMode::Video => "Video",
+    };
+    let repeat_mode = match repeat_mode {
+        RepeatMode::Off => "Off",
+        RepeatMode::One => "One",
+        RepeatMode::All => "All",
+    };
+    let shuffle = if is_shuffled { "On" } else { "Off" };
     frame.render_widget(
-        Paragraph::new("  ")
-            .right_aligned()
-            .fg(BORDER_FG)
-            .block(center_block),
-        status_area_center,
+        Paragraph::new(format!(
+            " Mode: [{playback_mode}]  Repeat: [{repeat_mode}]  Shuffle: [{shuffle}] "
+        ))
+        .left_aligned()
+        .style(theme.border())
+        .block(left_block),
+        status_area_left,
     );
+    if *player.is_nowplaying() {
+        render_progress(player, frame, center_block, status_area_center, theme);
+    } else {
+        frame.render_widget(
+            Paragraph::new("  ")
+                .left_aligned()
+                .style(theme.border())
+                .block(center_block),
+            status_area_center,
+        );
+    }
+    let volume = match player.volume() {
+        Some(volume) => format!(" Vol: {volume:.0}% "),
+        None => String::from("  "),
+    };
     frame.render_widget(
-        Paragraph::new("  ")
+        Paragraph::new(volume)
             .right_aligned()
-            .fg(BORDER_FG)
+            .style(theme.border())
             .block(right_block),
         status_area_right,
     );
 
     // ---------- status_bar
 }
+/// Playback progress of the current track: a gauge plus elapsed/total time.
+fn render_progress(
+    player: &Player,
+    frame: &mut Frame<'_>,
+    block: Block,
+    area: Rect,
+    theme: &Theme,
+) {
+    let elapsed = player.time_pos().unwrap_or(0.0);
+    let (ratio, total) = match player.duration() {
+        Some(duration) if duration > 0.0 => (
+            (elapsed / duration).clamp(0.0, 1.0),
+            format_timestamp(duration),
+        ),
+        // live streams and tracks that are still loading have no known duration
+        _ => (0.0, String::from("--:--")),
+    };
+    let paused = if player.is_paused() { "[Paused] " } else { "" };
+    let label = format!(" {paused}{} / {total} ", format_timestamp(elapsed));
+
+    frame.render_widget(
+        LineGauge::default()
+            .block(block)
+            .label(Span::styled(label, theme.border()))
+            .ratio(ratio)
+            .filled_style(theme.progress_filled())
+            .unfilled_style(theme.progress_unfilled()),
+        area,
+    );
+}
+
 fn render_content(
     frame: &mut Frame<'_>,
     content_area: Rect,
     mut list_state: ListState,
     videolist: &[Video],
+    playing_index: Option<usize>,
+    theme: &Theme,
 ) {
     //content
     let content_block_type = BorderType::Rounded;
-    let content_block_style = Style::new().fg(BORDER_FG);
+    let content_block_style = theme.border();
     let [content_block] = [Block::bordered()
         .border_type(content_block_type)
         .border_style(content_block_style)
@@ -144,15 +329,41 @@ fn render_content(
 
     let items: Vec<ListItem> = videolist
         .iter()
-        .map(|video| {
+        .enumerate()
+        .map(|(index, video)| {
+            // the entry that is playing right now stands out from the rest
+            let title_style = if playing_index == Some(index) {
+                theme.border().bold()
+            } else {
+                Style::new()
+            };
+            // channels, playlists and live streams have no length, a badge goes in its place
+            let length = match video.live_status.badge() {
+                _ if video.kind != ResultKind::Video => Span::styled(
+                    format!("{:>8}", video.kind.label().to_uppercase()),
+                    theme.border().bold(),
+                ),
+                Some(badge) => Span::styled(
+                    format!("{badge:>8}"),
+                    match video.live_status {
+                        LiveStatus::IsLive => theme.error().bold(),
+                        _ => theme.warning(),
+                    },
+                ),
+                None => Span::styled(format!("{:>8}", video.duration_label()), theme.subtext()),
+            };
             ListItem::new(Line::from(vec![
-                Span::from(format!(
-                    "{:<1$}",
-                    video.title,
-                    (content_area.width as usize).saturating_sub(30)
-                )),
+                Span::styled(
+                    fit_width(
+                        &video.title,
+                        (content_area.width as usize).saturating_sub(41),
+                    ),
+                    title_style,
+                ),
                 Span::styled(" | ", Style::new().dim()),
-                Span::styled(&video.uploader, Style::new().fg(SUBTEXT_FG)),
+                length,
+                Span::styled(" | ", Style::new().dim()),
+                Span::styled(&video.uploader, theme.subtext()),
             ]))
         })
         .collect();
@@ -162,7 +373,7 @@ fn render_content(
     frame.render_stateful_widget(
         List::new(items)
             .block(content_block)
-            .highlight_style(Style::new().fg(HIGHLIGHT_FG).bg(HIGHLIGHT_BG))
+            .highlight_style(theme.highlight())
             .highlight_symbol("> "),
         content_area,
         &mut list_state,
@@ -170,6 +381,292 @@ fn render_content(
     // ---------- content
 }
 
+fn render_playlists(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    mut list_state: ListState,
+    playlists: &[Playlist],
+    theme: &Theme,
+) {
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+        .padding(Padding::horizontal(1));
+
+    let items: Vec<ListItem> = playlists
+        .iter()
+        .map(|playlist| {
+            ListItem::new(Line::from(vec![
+                Span::from(playlist.name.as_str()),
+                Span::styled(" | ", Style::new().dim()),
+                Span::styled(format!("{} tracks", playlist.videos.len()), theme.subtext()),
+            ]))
+        })
+        .collect();
+
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(theme.highlight())
+            .highlight_symbol("> "),
+        area,
+        &mut list_state,
+    );
+}
+
+/// Past plays, newest first, with how long each played for and how long ago.
+fn render_history(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    mut list_state: ListState,
+    entries: &[&HistoryEntry],
+    filter: &str,
+    theme: &Theme,
+) {
+    let mut block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+        .padding(Padding::horizontal(1));
+    if !filter.is_empty() {
+        block = block
+            .title(format!(" Filter: {filter} "))
+            .title_style(theme.border().bold());
+    }
+
+    let now = history::now();
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::from(fit_width(
+                    &entry.video.title,
+                    (area.width as usize).saturating_sub(50),
+                )),
+                Span::styled(" | ", Style::new().dim()),
+                Span::styled(&entry.video.uploader, theme.subtext()),
+                Span::styled(" | ", Style::new().dim()),
+                Span::styled(
+                    format!(
+                        "{} played, {}",
+                        format_timestamp(entry.played_for),
+                        format_age(now.saturating_sub(entry.played_at))
+                    ),
+                    theme.subtext(),
+                ),
+            ]))
+        })
+        .collect();
+
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(theme.highlight())
+            .highlight_symbol("> "),
+        area,
+        &mut list_state,
+    );
+}
+
+/// Roughly how long ago `seconds` is, e.g. `5m ago` or `3d ago`.
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => String::from("just now"),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// Past warnings and errors, newest first.
+fn render_log(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    mut list_state: ListState,
+    log: &[Notification],
+    theme: &Theme,
+) {
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+        .padding(Padding::horizontal(1));
+
+    let now = history::now();
+    let items: Vec<ListItem> = log
+        .iter()
+        .map(|notification| {
+            let repeated = if notification.count > 1 {
+                format!(" (x{})", notification.count)
+            } else {
+                String::new()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<8}", notification.severity.label()),
+                    severity_style(notification.severity, theme),
+                ),
+                Span::from(format!("{}{repeated}", notification.message)),
+                Span::styled(" | ", Style::new().dim()),
+                Span::styled(
+                    format_age(now.saturating_sub(notification.raised_at)),
+                    theme.subtext(),
+                ),
+            ]))
+        })
+        .collect();
+
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(theme.highlight())
+            .highlight_symbol("> "),
+        area,
+        &mut list_state,
+    );
+}
+
+/// Toasts stacked up from the bottom right of `area`, newest at the bottom. Whatever doesn't fit
+/// stays in the log.
+fn render_notifications(toasts: &[Notification], frame: &mut Frame<'_>, area: Rect, theme: &Theme) {
+    let width = (area.width / 2).clamp(20.min(area.width), 60);
+    let mut bottom = area.bottom();
+    for toast in toasts.iter().rev() {
+        let message = if toast.count > 1 {
+            format!("{} (x{})", toast.message, toast.count)
+        } else {
+            toast.message.clone()
+        };
+        // borders and padding take 4 columns, the rest wraps
+        let text_width = width.saturating_sub(4).max(1) as usize;
+        let lines = message.chars().count().div_ceil(text_width).clamp(1, 4) as u16;
+        let height = lines + 2;
+        if bottom < area.top() + height {
+            break;
+        }
+        bottom -= height;
+        let toast_area = Rect::new(area.right() - width, bottom, width, height);
+        let style = severity_style(toast.severity, theme);
+        let popup = Popup::default()
+            .content(message)
+            .title(format!(" {} ", toast.severity.label()))
+            .title_style(style.bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(style)
+            .padding(Padding::horizontal(1));
+        frame.render_widget(popup, toast_area);
+    }
+}
+
+/// Spinner at the bottom left of `area` while a search runs.
+fn render_search_spinner(
+    query: &str,
+    is_loading_more: bool,
+    elapsed: Duration,
+    frame: &mut Frame<'_>,
+    area: Rect,
+    theme: &Theme,
+) {
+    const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    let spinner = FRAMES[(elapsed.as_millis() / 100) as usize % FRAMES.len()];
+    let text = if is_loading_more {
+        format!("{spinner} Loading more results for \"{query}\" (Esc to cancel)")
+    } else {
+        format!("{spinner} Searching for \"{query}\" (Esc to cancel)")
+    };
+    let width = (text.chars().count() as u16 + 4).min(area.width);
+    if area.height < 3 {
+        return;
+    }
+    let spinner_area = Rect::new(area.left(), area.bottom() - 3, width, 3);
+    let popup = Popup::default()
+        .content(text)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+        .style(theme.border())
+        .padding(Padding::horizontal(1));
+    frame.render_widget(popup, spinner_area);
+}
+
+/// `text` padded with spaces to `width` terminal columns, or cut short with an ellipsis when it's
+/// wider, so the columns after it line up.
+fn fit_width(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    if text.width() <= width {
+        fitted.push_str(text);
+        used = text.width();
+    } else if width > 0 {
+        for ch in text.chars() {
+            let ch_width = ch.width().unwrap_or(0);
+            // leave a column for the ellipsis
+            if used + ch_width >= width {
+                break;
+            }
+            fitted.push(ch);
+            used += ch_width;
+        }
+        fitted.push('…');
+        used += 1;
+    }
+    fitted.push_str(&" ".repeat(width - used));
+    fitted
+}
+
+fn severity_style(severity: Severity, theme: &Theme) -> Style {
+    match severity {
+        Severity::Info => theme.border(),
+        Severity::Warning => theme.warning(),
+        Severity::Error => theme.error(),
+    }
+}
+
+fn render_playlist_picker(
+    mut list_state: ListState,
+    playlists: &[Playlist],
+    frame: &mut Frame<'_>,
+    theme: &Theme,
+) {
+    // picker
+    let height = (playlists.len() as u16)
+        .saturating_add(2)
+        .min(frame.area().height);
+    let [_, picker_area, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(height),
+        Constraint::Fill(1),
+    ])
+    .areas(frame.area());
+    let [_, picker_area, _] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Percentage(40),
+        Constraint::Fill(1),
+    ])
+    .areas(picker_area);
+
+    let block = Block::bordered()
+        .title(" Add to Playlist ")
+        .title_style(theme.border().bold())
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+        .padding(Padding::horizontal(1));
+    let items: Vec<ListItem> = playlists
+        .iter()
+        .map(|playlist| ListItem::new(playlist.name.as_str()))
+        .collect();
+
+    frame.render_widget(Clear, picker_area);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(theme.highlight())
+            .highlight_symbol("> "),
+        picker_area,
+        &mut list_state,
+    );
+    //------------picker
+}
+
 fn render_header(
     now_playing: Video,
     is_nowplaying: bool,
@@ -177,12 +674,13 @@ fn render_header(
     tabs_titles: Vec<String>,
     frame: &mut Frame<'_>,
     header_area: Rect,
+    theme: &Theme,
 ) {
     let [left, right] =
         Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
             .areas(header_area);
     let block_type = BorderType::Rounded;
-    let block_style = Style::new().fg(BORDER_FG);
+    let block_style = theme.border();
     let left_block = Block::new()
         .borders(Borders::TOP | Borders::LEFT | Borders::BOTTOM)
         .border_type(block_type)
@@ -196,7 +694,7 @@ fn render_header(
         .padding("", "")
         .divider("")
         .block(left_block.clone())
-        .highlight_style(Style::new().fg(HIGHLIGHT_FG).bg(HIGHLIGHT_BG).bold())
+        .highlight_style(theme.highlight().bold())
         .select(Some(screen.current()));
 
     let now_playing = if is_nowplaying {
@@ -213,32 +711,52 @@ fn render_header(
     // ------------- header
 }
 
-fn render_search(search_query: String, frame: &mut Frame<'_>) {
-    // search
-    let [_, search_area, _] = Layout::vertical([
+fn render_search(search_query: String, frame: &mut Frame<'_>, theme: &Theme) {
+    render_prompt(" Search ", search_query, frame, theme);
+}
+
+fn render_prompt(title: &str, query: String, frame: &mut Frame<'_>, theme: &Theme) {
+    // prompt
+    let [_, prompt_area, _] = Layout::vertical([
         Constraint::Fill(1),
         Constraint::Length(3),
         Constraint::Fill(1),
     ])
     .areas(frame.area());
-    let [_, search_area, _] = Layout::horizontal([
+    let [_, prompt_area, _] = Layout::horizontal([
         Constraint::Fill(1),
         Constraint::Percentage(50),
         Constraint::Fill(1),
     ])
-    .areas(search_area);
+    .areas(prompt_area);
 
     let border_type = BorderType::Rounded;
-    let border_style = Style::new().fg(BORDER_FG).dim();
-    let title_style = Style::new().fg(BORDER_FG).bold().dim();
-    let search = Popup::default()
-        .content(format!(" {}", search_query))
-        .title(" Search ")
+    let border_style = theme.border().dim();
+    let title_style = theme.border().bold().dim();
+    let prompt = Popup::default()
+        .content(format!(" {}", query))
+        .title(title)
         .title_style(title_style)
         .borders(Borders::ALL)
         .border_type(border_type)
         .border_style(border_style)
         .padding(Padding::horizontal(1));
-    frame.render_widget(search, search_area);
-    //------------search
+    frame.render_widget(prompt, prompt_area);
+    //------------prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_titles_to_their_column() {
+        assert_eq!(fit_width("short", 8), "short   ");
+        assert_eq!(fit_width("exactly8", 8), "exactly8");
+        assert_eq!(fit_width("much too long", 8), "much to…");
+        assert_eq!(fit_width("日本語のタイトル", 8), "日本語… ");
+        assert_eq!(fit_width("日本語のタイトル", 8).width(), 8);
+        assert_eq!(fit_width("anything", 1), "…");
+        assert_eq!(fit_width("anything", 0), "");
+    }
 }